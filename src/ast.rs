@@ -1,4 +1,4 @@
-use crate::{error::CompilerError, syntax::use_item, token::TokenStream};
+use crate::{error::CompilerError, syntax::{use_item, Import, PathRoot}, token::TokenStream};
 
 mod path;
 pub use path::Path;
@@ -6,7 +6,10 @@ pub use path::Path;
 pub fn parse_path(source: &str) -> std::result::Result<Path, CompilerError> {
     let buffer = TokenStream::buffer(source);
     let tokens = TokenStream::new(&buffer, source);
-    let (_, path) = use_item(tokens)?;
+    let (_, item) = use_item(tokens)?;
 
-    Ok(path.path.iter().into())
+    match (item.root(), item.imports().as_slice()) {
+        (PathRoot::Source, [Import::Single { path, .. }]) => Ok(path.iter().into()),
+        _ => Err(CompilerError::include_error(source)),
+    }
 }
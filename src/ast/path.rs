@@ -38,7 +38,7 @@ impl Path {
         self.slices.pop().is_some()
     }
 
-    pub fn as_slice(&self) -> PathSlice {
+    pub fn as_slice(&self) -> PathSlice<'_> {
         PathSlice {
             path: self,
             rem: &self.slices[..],
@@ -68,12 +68,15 @@ impl<S: AsRef<str>, I: Iterator<Item=S>> From<I> for Path {
 
 impl PartialEq for Path {
     fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
         for (lhs, rhs) in self.components().zip(other.components()) {
             if lhs != rhs {
                 return false;
             }
         }
-        return true;
+        true
     }
 }
 
@@ -87,6 +90,18 @@ impl Hash for Path {
     }
 }
 
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, component) in self.components().enumerate() {
+            if i > 0 {
+                write!(f, "::")?;
+            }
+            write!(f, "{}", component)?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Debug for Path {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_empty() {
@@ -110,6 +125,14 @@ impl fmt::Debug for Path {
     }
 }
 
+/// A path is serialized as the list of its components.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Path {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.components())
+    }
+}
+
 pub struct PathSlice<'a> {
     path: &'a Path,
     rem: &'a [[usize; 2]],
@@ -122,7 +145,7 @@ impl PathSlice<'_> {
         }
         self.rem = &self.rem[..self.rem.len() - 2];
 
-        self.rem.len() >= 1
+        !self.rem.is_empty()
     }
 
     pub fn component(&self, i: usize) -> Option<&str> {
@@ -182,4 +205,11 @@ mod test {
         assert_eq!(p1, p2);
         assert_eq!(p2, p1);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn path_serialize() {
+        let path = parse_path("use main::vert;").unwrap();
+        assert_eq!(serde_json::to_string(&path).unwrap(), r#"["main","vert"]"#);
+    }
 }
@@ -0,0 +1,107 @@
+//! Formats shader sources in place, or with `--check` lists the ones which
+//! are not formatted and fails.
+//!
+//!     xenofmt [--check] [--extension <ext>] <path>...
+//!
+//! Directories are searched for modules the way the compiler finds them:
+//! files with the extension, `xs` unless given, which are not excluded by
+//! the directory's `.xsignore`.
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
+
+use xenovisor_shaderc::{format::format_file, session::Session, span::SourceFile};
+
+const USAGE: &str = "usage: xenofmt [--check] [--extension <ext>] <path>...";
+
+fn main() -> ExitCode {
+    let mut check = false;
+    let mut session = Session::new();
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--extension" => match args.next() {
+                Some(extension) => session.set_extension(extension.trim_start_matches('.')),
+                None => {
+                    eprintln!("missing extension\n{}", USAGE);
+                    return ExitCode::from(2);
+                },
+            },
+            flag if flag.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", flag, USAGE);
+                return ExitCode::from(2);
+            },
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if let Err(err) = sources(&session, path, &mut files) {
+            eprintln!("{}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut failed = false;
+    for file in files {
+        match format(&file, check) {
+            Ok(true) => {},
+            Ok(false) => {
+                println!("Would reformat {}", file.display());
+                failed = true;
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Format a file, or only check it is formatted. Returns whether the file
+/// is left formatted.
+fn format(file: &Path, check: bool) -> Result<bool, String> {
+    let text = fs::read_to_string(file)
+        .map_err(|err| format!("{}: {}", file.display(), err))?;
+    let source = Arc::new(SourceFile::new(file.display().to_string(), text));
+    let formatted = format_file(&source)
+        .map_err(|err| err.to_string())?;
+    if formatted == source.text() {
+        return Ok(true);
+    }
+    if check {
+        return Ok(false);
+    }
+    fs::write(file, formatted)
+        .map_err(|err| format!("{}: {}", file.display(), err))?;
+    Ok(true)
+}
+
+fn sources(session: &Session, path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+    let found = session.discover_files(path)
+        .map_err(|err| err.to_string())?;
+    files.extend(found);
+    Ok(())
+}
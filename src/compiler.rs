@@ -1,9 +1,12 @@
 use crate::config;
+use crate::glsl;
 use crate::driver::Driver;
 use crate::error::Result;
 use crate::hir;
-use crate::session::Session;
+use crate::session::{FileSystem, Session};
+use crate::syntax;
 
+use std::borrow::Cow;
 use std::hash::Hash;
 use std::iter::IntoIterator;
 use std::path::{Path, PathBuf};
@@ -14,6 +17,10 @@ use std::sync::Arc;
 
 pub trait Writer {
     fn write<R: Read>(&mut self, target: &config::Target, stage: &config::ShaderStage, contents: R) -> io::Result<()>;
+
+    /// Write the intermediate output of code generation, which is kept apart
+    /// from the shaders of each stage.
+    fn write_dump<R: Read>(&mut self, format: &config::DumpFormat, contents: R) -> io::Result<()>;
 }
 
 pub struct DefaultWriter {
@@ -26,6 +33,14 @@ impl DefaultWriter {
             root,
         }
     }
+
+    fn create<R: Read>(&mut self, name: &str, mut contents: R) -> io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        self.root.push(name);
+        let file = fs::File::create(&self.root).and_then(|mut file| io::copy(&mut contents, &mut file));
+        self.root.pop();
+        file.map(|_| ())
+    }
 }
 
 impl Writer for DefaultWriter {
@@ -33,7 +48,7 @@ impl Writer for DefaultWriter {
         &mut self,
         target: &config::Target,
         stage: &config::ShaderStage,
-        mut contents: R,
+        contents: R,
     ) -> io::Result<()> {
         match target {
             config::Target::Glsl => {},
@@ -43,39 +58,65 @@ impl Writer for DefaultWriter {
             config::ShaderStage::Fragment => "frag.glsl",
             config::ShaderStage::Vertex => "vert.glsl",
         };
+        self.create(suffix, contents)
+    }
 
-        fs::create_dir_all(&self.root)?;
-        self.root.push(suffix);
-        let mut file = fs::File::create(&self.root)?;
-        io::copy(&mut contents, &mut file)?;
-        self.root.pop();
-
-        Ok(())
+    fn write_dump<R: Read>(&mut self, format: &config::DumpFormat, contents: R) -> io::Result<()> {
+        let name = match format {
+            config::DumpFormat::Debug => "ast.txt",
+            #[cfg(feature = "serde")]
+            config::DumpFormat::Json => "ast.json",
+        };
+        self.create(name, contents)
     }
 }
 
-pub struct GlslBackend; impl GlslBackend { fn new() -> GlslBackend { GlslBackend } }
+pub struct GlslBackend;
+
+impl GlslBackend {
+    fn new() -> GlslBackend {
+        GlslBackend
+    }
+
+    /// The name to emit for a source identifier.
+    pub fn identifier<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        glsl::mangle(name)
+    }
+}
 
 /// Entry point for dynamic runtime use.
 pub struct Compiler {
     backend: GlslBackend,
     driver: Driver,
     session: Session,
+    config: config::Config,
 }
 
 impl Compiler {
     /// Begin a new compiler session.
-    pub fn open(_config: config::Config) -> Compiler {
+    pub fn open(config: config::Config) -> Compiler {
         let backend = GlslBackend::new();
         let driver = Driver::new();
-        let session = Session::new();
+        let mut session = Session::new();
+        session.set_extension(config.extension());
+        for (name, value) in config.env() {
+            session.declare_default(name.clone(), value.clone());
+        }
         Compiler {
             backend,
             driver,
             session,
+            config,
         }
     }
 
+    /// Read sources through `fs` instead of the real filesystem, such as
+    /// to compile an editor's unsaved buffers.
+    pub fn with_file_system(mut self, fs: Arc<dyn FileSystem>) -> Compiler {
+        self.session.set_file_system(fs);
+        self
+    }
+
     /// Feed the compiler a set of inputs.
     pub fn feed<I: IntoIterator<Item=config::Input>>(&mut self, inputs: I) -> Result<()> {
         for i in inputs {
@@ -84,12 +125,16 @@ impl Compiler {
         Ok(())
     }
 
-    pub fn declare<S1: AsRef<str>, S2: AsRef<str>, I: IntoIterator<Item=(S1, config::EnvVar<S2>)>>(&self, _env: I) {
-
+    /// Give values to the `declare const` items of the shaders, such as
+    /// feature flags. These replace defaults set in the config.
+    pub fn declare<S1: AsRef<str>, S2: AsRef<str>, I: IntoIterator<Item=(S1, config::EnvVar<S2>)>>(&mut self, env: I) {
+        for (name, value) in env {
+            self.session.declare(name.as_ref().to_owned(), value.into_owned());
+        }
     }
 
     /// Query the compiler to generate code or perform analysis.
-    pub fn query(&self) -> Queries {
+    pub fn query(&self) -> Queries<'_> {
         Queries {
             compiler: self,
         }
@@ -111,8 +156,9 @@ impl<'c> Queries<'c> {
             pipeline.fragment.expect("must declare frag shader"),
         ];
         let asts = self.compiler.driver.ast(&self.compiler.session, &pipeline)?;
-        let contents: String = asts.map(|a| format!("{:#?}\n", a)).collect();
-        w.write(&config::Target::Glsl, &config::ShaderStage::Vertex, contents.as_bytes())?;
+        let format = self.compiler.config.dump();
+        let contents = dump(format, asts)?;
+        w.write_dump(&format, contents.as_bytes())?;
         // let valid = self.linker().validate_pipeline(vs, fs);
         // self.validate_pipeline(&vs, &fs);
         // there will be internal HIR info built but the returned structure
@@ -158,6 +204,23 @@ impl<'c> Queries<'c> {
     }
 }
 
+/// Write out syntax trees in the given format.
+fn dump<I: Iterator<Item=Arc<syntax::Module>>>(format: config::DumpFormat, asts: I) -> Result<String> {
+    match format {
+        config::DumpFormat::Debug => Ok(asts.map(|a| format!("{:#?}\n", a)).collect()),
+        #[cfg(feature = "serde")]
+        config::DumpFormat::Json => {
+            let asts: Vec<Arc<syntax::Module>> = asts.collect();
+            let asts: Vec<&syntax::Module> = asts.iter().map(|a| &**a).collect();
+            serde_json::to_string_pretty(&asts)
+                .map_err(|err| {
+                    let message = format!("failed to serialize syntax trees: {}", err);
+                    crate::error::CompilerError::ice(message, crate::error::CompilerStage::Parsing)
+                })
+        },
+    }
+}
+
 pub struct Generator {
     compiler: Compiler,
 }
@@ -170,9 +233,9 @@ impl Generator {
             vertex: Some(vertex),
             fragment: Some(fragment),
         };
-        self.compiler.query()
-            .run_code_gen(pipeline, w)
-            .expect("failed code gen");
+        if let Err(error) = self.compiler.query().run_code_gen(pipeline, w) {
+            panic!("failed code gen:\n{}", error);
+        }
     }
 
     /// Generates Rust code to generate shaders from fragments, without full
@@ -198,10 +261,50 @@ where P: AsRef<Path>,
     S1: AsRef<str>,
     S2: AsRef<str>,
 {
-    let config = config.read();
+    let config = config.read()
+        .expect("error reading config");
+    let mut inputs = vec![config::Input::Path(root.as_ref().to_owned())];
+    inputs.extend(config.roots().iter().cloned().map(config::Input::Path));
     let mut compiler = Compiler::open(config);
     compiler.declare(env);
-    compiler.feed([config::Input::Path(root.as_ref().to_owned())])
+    compiler.feed(inputs)
         .expect("error feeding file");
     Generator { compiler }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Keeps the dump, and fails if anything is written for a stage.
+    #[derive(Default)]
+    struct Dump {
+        contents: String,
+    }
+
+    impl Writer for &mut Dump {
+        fn write<R: Read>(&mut self, _target: &config::Target, _stage: &config::ShaderStage, _contents: R) -> io::Result<()> {
+            panic!("the dump was written as a shader stage");
+        }
+
+        fn write_dump<R: Read>(&mut self, format: &config::DumpFormat, mut contents: R) -> io::Result<()> {
+            assert_eq!(*format, config::DumpFormat::Debug);
+            contents.read_to_string(&mut self.contents)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_dump_apart_from_stages() {
+        let mut compiler = Compiler::open(config::Config::default());
+        compiler.feed([config::Input::Source {
+            module: "main".into(),
+            text: "fn vert() {} fn frag() {}".into(),
+        }]).unwrap();
+
+        let mut dump = Dump::default();
+        let pipeline = config::Pipeline { vertex: Some("use main::vert;"), fragment: Some("use main::frag;") };
+        compiler.query().run_code_gen(pipeline, &mut dump).unwrap();
+        assert!(dump.contents.contains("Module"), "{}", dump.contents);
+    }
+}
@@ -1,48 +1,210 @@
-use std::default::Default;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::error::Result;
+#[cfg(not(feature = "serde"))]
+use crate::{error::InputError, span::Annotations};
+
+#[cfg(feature = "serde")]
+mod load;
+
+/// The extension of shader modules, unless a config sets another.
+pub const DEFAULT_EXTENSION: &str = "xs";
+
 pub trait ConfigSource {
-    fn read(self) -> Config;
+    fn read(self) -> Result<Config>;
 }
 
 impl ConfigSource for Config {
-    fn read(self) -> Config {
-        self
+    fn read(self) -> Result<Config> {
+        Ok(self)
     }
 }
 
+/// Reads a `.toml` or `.json` config file, such as a checked in
+/// `shaderc.toml`. Relative paths in the file are taken from the directory
+/// holding it. Without the `serde` feature, reading a file is an error.
 impl<T: AsRef<Path>> ConfigSource for T {
-    fn read(self) -> Config {
-        unimplemented!("TODO: use serde feature!")
+    fn read(self) -> Result<Config> {
+        #[cfg(feature = "serde")]
+        let config = load::read(self.as_ref());
+        #[cfg(not(feature = "serde"))]
+        let config = {
+            let message = format!("{}: loading config files requires the `serde` feature", self.as_ref().display());
+            Err(InputError::Config(Annotations::from_error_message(message)).into())
+        };
+        config
     }
 }
 
+/// Settings for a build. Every field may be left out of a config file.
+///
+/// ```toml
+/// roots = ["shaders"]
+/// extension = "xs"
+/// output = "build/shaders"
+///
+/// [target]
+/// language = "glsl"
+/// version = 450
+///
+/// [pipelines.main]
+/// vertex = "main::vert"
+/// fragment = "main::frag"
+///
+/// [env]
+/// SHADOWS = true
+///
+/// [lints]
+/// unused_imports = "deny"
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct Config {
+    roots: Vec<PathBuf>,
+    extension: Option<String>,
+    target: TargetOptions,
+    output: Option<PathBuf>,
+    pipelines: BTreeMap<String, Pipeline<String>>,
+    env: BTreeMap<String, EnvVar<String>>,
+    lints: BTreeMap<String, LintLevel>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dump: DumpFormat,
 }
 
 impl Config {
     pub fn empty_env() -> impl IntoIterator<Item=(&'static str, EnvVar<&'static str>)> {
         []
     }
+
+    /// Set how the intermediate output of code generation is written.
+    pub fn with_dump(mut self, dump: DumpFormat) -> Config {
+        self.dump = dump;
+        self
+    }
+
+    pub fn dump(&self) -> DumpFormat {
+        self.dump
+    }
+
+    /// The directories searched for shader modules.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// The extension of shader modules under the roots. Other files are
+    /// not modules.
+    pub fn extension(&self) -> &str {
+        self.extension.as_deref().unwrap_or(DEFAULT_EXTENSION)
+    }
+
+    pub fn target(&self) -> &TargetOptions {
+        &self.target
+    }
+
+    /// The directory generated shaders are written to.
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// The pipelines to build, by name.
+    pub fn pipelines(&self) -> &BTreeMap<String, Pipeline<String>> {
+        &self.pipelines
+    }
+
+    /// The values of declared constants which the compiler is not given.
+    pub fn env(&self) -> &BTreeMap<String, EnvVar<String>> {
+        &self.env
+    }
+
+    /// The level set for a lint, if the config sets one.
+    pub fn lint(&self, name: &str) -> Option<LintLevel> {
+        self.lints.get(name).copied()
+    }
+
+    pub fn lints(&self) -> &BTreeMap<String, LintLevel> {
+        &self.lints
+    }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {}
+/// The format of the intermediate output written by code generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DumpFormat {
+    /// The `Debug` text of each syntax tree.
+    #[default]
+    Debug,
+    /// A JSON array with one syntax tree per module.
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// The language generated, and the version of it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct TargetOptions {
+    language: Target,
+    version: Option<u32>,
+}
+
+impl TargetOptions {
+    pub fn language(&self) -> &Target {
+        &self.language
+    }
+
+    /// The version of the language, e.g. `450` for GLSL 4.50. When unset,
+    /// the backend picks one.
+    pub fn version(&self) -> Option<u32> {
+        self.version
     }
 }
 
+/// How a lint which fires is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
 pub enum Input {
+    /// A directory searched for modules.
     Path(PathBuf),
+    /// The text of one module, such as `lighting::pbr`, which is kept in
+    /// memory instead of read from a file.
+    Source {
+        module: String,
+        text: String,
+    },
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum EnvVar<S> {
     String(S),
     Integer(i32),
+    Float(f64),
     Bool(bool),
 }
 
+impl<S: AsRef<str>> EnvVar<S> {
+    pub fn into_owned(self) -> EnvVar<String> {
+        match self {
+            EnvVar::String(s) => EnvVar::String(s.as_ref().to_owned()),
+            EnvVar::Integer(i) => EnvVar::Integer(i),
+            EnvVar::Float(f) => EnvVar::Float(f),
+            EnvVar::Bool(b) => EnvVar::Bool(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Target {
+    #[default]
     Glsl,
 }
 
@@ -51,6 +213,9 @@ pub enum ShaderStage {
     Fragment,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Pipeline<S> {
     pub vertex: Option<S>,
     pub fragment: Option<S>,
@@ -59,3 +224,15 @@ pub struct Pipeline<S> {
 pub struct Fragment {
 
 }
+
+/// Config files are read in `load` when the `serde` feature is enabled.
+#[cfg(all(test, not(feature = "serde")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requires_serde_to_read_files() {
+        let err = "shaderc.toml".read().unwrap_err();
+        assert!(err.to_string().contains("loading config files requires the `serde` feature"), "{}", err);
+    }
+}
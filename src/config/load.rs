@@ -0,0 +1,224 @@
+use std::{convert::TryFrom, fmt, fs, path::Path, sync::Arc};
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Unexpected, Visitor};
+
+use crate::{
+    error::{CompilerError, InputError, Result},
+    span::{Annotations, ByteSpan, SourceFile, SourcePos},
+};
+
+use super::{Config, EnvVar};
+
+/// Read a config file, choosing the format by its extension.
+pub(super) fn read(path: &Path) -> Result<Config> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| config_error(Annotations::from_error_message(format!("{}: {}", path.display(), err))))?;
+    let source = Arc::new(SourceFile::new(path.display().to_string(), text));
+    let mut config: Config = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => from_toml(&source)?,
+        Some("json") => from_json(&source)?,
+        _ => {
+            let message = format!("{}: config files must be `.toml` or `.json`", path.display());
+            return Err(config_error(Annotations::from_error_message(message)));
+        },
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for root in &mut config.roots {
+        *root = dir.join(&*root);
+    }
+    if let Some(output) = &mut config.output {
+        *output = dir.join(&*output);
+    }
+    Ok(config)
+}
+
+pub(super) fn from_toml<T: DeserializeOwned>(source: &Arc<SourceFile>) -> Result<T> {
+    let deserializer = toml::Deserializer::new(source.text());
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let span = err.inner().span().map(ByteSpan::from);
+        schema_error(source, &err.path().to_string(), err.inner().message(), span)
+    })
+}
+
+pub(super) fn from_json<T: DeserializeOwned>(source: &Arc<SourceFile>) -> Result<T> {
+    let mut deserializer = serde_json::Deserializer::from_str(source.text());
+    let located = |err: &serde_json::Error, path: &str| {
+        // serde_json counts lines and columns from one, and appends them to
+        // its messages
+        let message = err.to_string();
+        let message = message.rsplit_once(" at line ").map_or(&*message, |(message, _)| message);
+        let span = (err.line() > 0).then(|| {
+            let position = SourcePos::new(err.line() as u32 - 1, err.column().saturating_sub(1) as u32);
+            let offset = source.offset(position);
+            ByteSpan::new(offset, offset)
+        });
+        schema_error(source, path, message, span)
+    };
+    let value = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|err| located(err.inner(), &err.path().to_string()))?;
+    deserializer.end()
+        .map_err(|err| located(&err, "."))?;
+    Ok(value)
+}
+
+/// Env values are told apart by their type in the file, so an integer out of
+/// the `i32` range is an error instead of a float.
+impl<'de> Deserialize<'de> for EnvVar<String> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EnvVarVisitor;
+
+        impl<'de> Visitor<'de> for EnvVarVisitor {
+            type Value = EnvVar<String>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string, boolean, float or 32-bit integer")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> std::result::Result<Self::Value, E> {
+                Ok(EnvVar::Bool(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<Self::Value, E> {
+                i32::try_from(value)
+                    .map(EnvVar::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<Self::Value, E> {
+                i32::try_from(value)
+                    .map(EnvVar::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> std::result::Result<Self::Value, E> {
+                Ok(EnvVar::Float(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+                Ok(EnvVar::String(value.to_owned()))
+            }
+        }
+
+        deserializer.deserialize_any(EnvVarVisitor)
+    }
+}
+
+/// An error in a config file, at the field named by `path`.
+fn schema_error(source: &Arc<SourceFile>, path: &str, message: &str, span: Option<ByteSpan>) -> CompilerError {
+    let message = if path == "." {
+        message.to_owned()
+    } else {
+        format!("invalid `{}`: {}", path, message)
+    };
+    let annotations = match span {
+        Some(span) => Annotations::error(source, span, message),
+        None => Annotations::from_error_message(format!("{}: {}", source.name(), message)),
+    };
+    config_error(annotations)
+}
+
+fn config_error(annotations: Annotations) -> CompilerError {
+    CompilerError::Input(InputError::Config(annotations))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{EnvVar, LintLevel, Target};
+
+    fn source(name: &str, text: &str) -> Arc<SourceFile> {
+        Arc::new(SourceFile::new(name.into(), text.into()))
+    }
+
+    #[test]
+    fn reads_toml() {
+        let config: Config = from_toml(&source("shaderc.toml", "
+            roots = [\"shaders\"]
+            extension = \"shader\"
+            output = \"build\"
+
+            [target]
+            language = \"glsl\"
+            version = 450
+
+            [pipelines.main]
+            vertex = \"main::vert\"
+            fragment = \"main::frag\"
+
+            [env]
+            SHADOWS = true
+            QUALITY = 2
+            GAMMA = 2.2
+            NAME = \"forward\"
+
+            [lints]
+            unused_imports = \"deny\"
+        ")).unwrap();
+        assert_eq!(config.roots(), [Path::new("shaders")]);
+        assert_eq!(config.extension(), "shader");
+        assert_eq!(config.target().language(), &Target::Glsl);
+        assert_eq!(config.target().version(), Some(450));
+        assert_eq!(config.pipelines()["main"].fragment.as_deref(), Some("main::frag"));
+        assert_eq!(config.env()["SHADOWS"], EnvVar::Bool(true));
+        assert_eq!(config.env()["QUALITY"], EnvVar::Integer(2));
+        assert_eq!(config.env()["GAMMA"], EnvVar::Float(2.2));
+        assert_eq!(config.env()["NAME"], EnvVar::String("forward".into()));
+        assert_eq!(config.lint("unused_imports"), Some(LintLevel::Deny));
+        assert_eq!(config.lint("dead_code"), None);
+    }
+
+    #[test]
+    fn reads_json() {
+        let config: Config = from_json(&source("shaderc.json", r#"{
+            "roots": ["a", "b"],
+            "pipelines": { "main": { "vertex": "main::vert" } }
+        }"#)).unwrap();
+        assert_eq!(config.roots().len(), 2);
+        assert_eq!(config.extension(), "xs");
+        assert_eq!(config.pipelines()["main"].fragment, None);
+        assert_eq!(config.target().version(), None);
+    }
+
+    #[test]
+    fn locates_schema_errors() {
+        let err = from_toml::<Config>(&source("shaderc.toml", "roots = []\n[target]\nversion = \"new\"\n")).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("shaderc.toml:3:11"), "{}", message);
+        assert!(message.contains("invalid `target.version`"), "{}", message);
+
+        let err = from_toml::<Config>(&source("shaderc.toml", "[lints]\nunused = \"loud\"\n")).unwrap_err();
+        assert!(err.to_string().contains("invalid `lints.unused`"), "{}", err);
+
+        let err = from_json::<Config>(&source("shaderc.json", "{\n  \"root\": []\n}")).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("shaderc.json:2:"), "{}", message);
+        assert!(message.contains("unknown field `root`"), "{}", message);
+
+        let err = from_toml::<Config>(&source("shaderc.toml", "[env]\nMAX = 3000000000\n")).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("shaderc.toml:2:7"), "{}", message);
+        assert!(message.contains("invalid `env.MAX`"), "{}", message);
+
+        let err = from_json::<Config>(&source("shaderc.json", "{\"env\": {\"MAX\": -3000000000}}")).unwrap_err();
+        assert!(err.to_string().contains("invalid `env.MAX`"), "{}", err);
+    }
+
+    #[test]
+    fn resolves_paths_from_the_file() {
+        let root = std::env::temp_dir().join(format!("xenovisor-config-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let file = root.join("shaderc.toml");
+        fs::write(&file, "roots = [\"shaders\"]\noutput = \"build\"\n").unwrap();
+
+        let config = read(&file).unwrap();
+        assert_eq!(config.roots(), [root.join("shaders")]);
+        assert_eq!(config.output(), Some(&*root.join("build")));
+
+        let yaml = root.join("shaderc.yaml");
+        fs::write(&yaml, "roots: [shaders]\n").unwrap();
+        let err = read(&yaml).unwrap_err();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(err.to_string().contains("config files must be `.toml` or `.json`"), "{}", err);
+    }
+}
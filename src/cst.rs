@@ -0,0 +1,760 @@
+//! A lossless concrete syntax tree.
+//!
+//! The tree keeps every byte of the source, including whitespace and
+//! comments, so the text can always be reconstructed exactly. It comes in two
+//! layers: the green tree is immutable and position independent, so unchanged
+//! subtrees can be shared between versions of a file, while the red tree
+//! (`SyntaxNode`, `SyntaxToken`) wraps it with absolute offsets and parent
+//! links for navigation.
+//!
+//! The tree is shallow: a module holds one node per item, and the typed AST
+//! in `syntax` is built by parsing the tokens of each item node.
+
+use std::{fmt, sync::Arc};
+
+use logos::{Logos, Span};
+
+use crate::{span::ByteSpan, token::{Token, TokenStream}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Module,
+    /// An item, with its attributes, doc comments, and any trivia before it.
+    Item,
+    /// A (possibly nested) block comment, which runs to the end of the
+    /// source if it is never closed.
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenToken {
+    kind: Token,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: Token, text: String) -> GreenToken {
+        GreenToken {
+            kind,
+            text,
+        }
+    }
+
+    pub fn kind(&self) -> &Token {
+        &self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenNode {
+    kind: NodeKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> GreenNode {
+        let len = children.iter().map(GreenElement::len).sum();
+        GreenNode {
+            kind,
+            len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// The length of the node's text in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len(),
+            GreenElement::Token(token) => token.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A node of the red tree: a green node at a position in the source.
+#[derive(Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Arc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind()
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// The byte range of the node, including all of its trivia.
+    pub fn span(&self) -> ByteSpan {
+        ByteSpan::new(self.0.offset, self.0.offset + self.0.green.len())
+    }
+
+    /// The byte range from the first to the last token which is not trivia,
+    /// or `None` if the node is all trivia.
+    pub fn significant_span(&self) -> Option<ByteSpan> {
+        let tokens = self.tokens();
+        let first = tokens.iter().find(|token| !token.is_trivia())?;
+        let last = tokens.iter().rfind(|token| !token.is_trivia())?;
+        Some(first.span().to(last.span()))
+    }
+
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0.green.children()
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Arc::new(NodeData {
+                        green: green.clone(),
+                        offset,
+                        parent: Some(self.clone()),
+                    }))),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: green.clone(),
+                        offset,
+                        parent: self.clone(),
+                    }),
+                };
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    pub fn child_nodes(&self) -> Vec<SyntaxNode> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every token within the node, in source order.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The node's source text, exactly as written.
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.span().range())
+    }
+}
+
+/// A token of the red tree.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+    pub fn green(&self) -> &Arc<GreenToken> {
+        &self.green
+    }
+
+    pub fn kind(&self) -> &Token {
+        self.green.kind()
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        ByteSpan::new(self.offset, self.offset + self.green.len())
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    /// Whitespace and comments, which the parser skips.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind(), Token::Whitespace | Token::LineComment) || self.parent.kind() == NodeKind::Comment
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?} {:?}", self.kind(), self.span().range(), self.text())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    /// The kind of node, or `None` for a token.
+    pub fn kind(&self) -> Option<NodeKind> {
+        match self {
+            SyntaxElement::Node(node) => Some(node.kind()),
+            SyntaxElement::Token(_) => None,
+        }
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(token) => token.span(),
+        }
+    }
+
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        match self {
+            SyntaxElement::Node(node) => node.tokens(),
+            SyntaxElement::Token(token) => vec![token.clone()],
+        }
+    }
+}
+
+/// Build the syntax tree of a module.
+///
+/// Tokens are grouped into items the same way the parser recovers from
+/// errors: an item ends at a `;` or closing `}` at the top level, or where a
+/// token which can only begin an item appears. Trivia between items belongs
+/// to the item after it, except for a comment on the same line as the end of
+/// an item, which stays with that item. Trivia after the last item belongs
+/// to the module.
+pub fn parse(source: &str) -> SyntaxNode {
+    SyntaxNode::new_root(Arc::new(green_module(source)))
+}
+
+pub fn green_module(source: &str) -> GreenNode {
+    group(source, &TokenStream::lossless_buffer(source))
+}
+
+/// A change to source text: the bytes in `span` are replaced with `text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    span: ByteSpan,
+    text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: ByteSpan, text: String) -> TextEdit {
+        TextEdit {
+            span,
+            text,
+        }
+    }
+
+    pub fn insert(offset: usize, text: String) -> TextEdit {
+        TextEdit::new(ByteSpan::new(offset, offset), text)
+    }
+
+    pub fn delete(span: ByteSpan) -> TextEdit {
+        TextEdit::new(span, String::new())
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// How much longer the source is after the edit.
+    pub fn delta(&self) -> isize {
+        self.text.len() as isize - self.span.len() as isize
+    }
+
+    /// Whether the edit replaces a range of `source` which starts and ends
+    /// on character boundaries.
+    pub fn applies_to(&self, source: &str) -> bool {
+        self.span.start <= self.span.end
+            && source.is_char_boundary(self.span.start)
+            && source.is_char_boundary(self.span.end)
+    }
+
+    /// The edited source. Panics unless the edit `applies_to` the source.
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = source.to_owned();
+        edited.replace_range(self.span.range(), &self.text);
+        edited
+    }
+}
+
+/// Apply an edit to a syntax tree. Only the tokens around the edit are lexed
+/// again, and only the items they belong to are grouped again: the others
+/// are shared with the old tree, so `Arc::ptr_eq` on their green nodes tells
+/// which items changed.
+pub fn reparse(tree: &SyntaxNode, edit: &TextEdit) -> SyntaxNode {
+    let source = edit.apply(&tree.text());
+    let old: Vec<(Token, Span)> = tree.tokens()
+        .iter()
+        .map(|token| (token.kind().clone(), token.span().range()))
+        .collect();
+    let (tokens, relexed) = relex(&old, &source, edit);
+
+    // an item ends depending on the token after it, and takes a comment on
+    // the same line as its end, so an edit up to the first token of an item
+    // may change the item before it
+    let children = tree.children();
+    let is_item = |child: &SyntaxElement| child.kind() == Some(NodeKind::Item);
+    let mut first = children.iter()
+        .rposition(|child| child.span().start <= relexed.start)
+        .unwrap_or(0);
+    let significant = match children.get(first) {
+        Some(SyntaxElement::Node(node)) if node.kind() == NodeKind::Item => node.significant_span(),
+        _ => None,
+    };
+    if significant.is_none_or(|span| span.start >= relexed.start) {
+        first = children[..first].iter().rposition(is_item).unwrap_or(0);
+    }
+    let start = children.get(first).map(|child| child.span().start).unwrap_or(0);
+
+    let old_children = tree.green().children();
+    let mut green = old_children[..first].to_vec();
+    let mut grouper = Grouper::new(&source, &tokens, start);
+    let next = tokens.iter().position(|(_, span)| span.start >= start).unwrap_or(tokens.len());
+    for i in next..tokens.len() {
+        let end = match grouper.push(i) {
+            Some(end) if end >= relexed.end => end,
+            _ => continue,
+        };
+        // past the edit, grouping from an old item boundary gives the old items
+        let resume = children[first..]
+            .iter()
+            .position(|child| is_item(child) && child.span().start.checked_add_signed(edit.delta()) == Some(end));
+        if let Some(resume) = resume {
+            green.append(&mut grouper.children);
+            green.extend_from_slice(&old_children[first + resume..]);
+            return SyntaxNode::new_root(Arc::new(GreenNode::new(NodeKind::Module, green)));
+        }
+    }
+    green.extend(grouper.finish());
+    SyntaxNode::new_root(Arc::new(GreenNode::new(NodeKind::Module, green)))
+}
+
+/// Lex the edited source, starting a little before the edit and stopping
+/// once the new tokens line up with the old ones again. Returns the tokens of
+/// the edited source, and the range of it which was lexed again.
+fn relex(old: &[(Token, Span)], source: &str, edit: &TextEdit) -> (Vec<(Token, Span)>, Span) {
+    let delta = edit.delta();
+    let edit_end = edit.span().end;
+    // the lexer may look past the end of a token, so the token before the
+    // first one touching the edit is lexed again too
+    let first = old.iter()
+        .position(|(_, span)| span.end >= edit.span().start)
+        .unwrap_or(old.len())
+        .saturating_sub(1);
+    let start = old.get(first).map(|(_, span)| span.start).unwrap_or(0);
+
+    let mut tokens = old[..first].to_vec();
+    let mut next_old = first;
+    for (token, span) in Token::lexer(&source[start..]).spanned() {
+        let span = span.start + start..span.end + start;
+        let end = span.end;
+        tokens.push((token, span));
+
+        while next_old < old.len() && old[next_old].1.end.saturating_add_signed(delta) < end {
+            next_old += 1;
+        }
+        let aligned = old.get(next_old)
+            .is_some_and(|(_, span)| span.end >= edit_end && span.end.saturating_add_signed(delta) == end);
+        if aligned {
+            let shifted = old[next_old + 1..]
+                .iter()
+                .map(|(token, span)| {
+                    (token.clone(), span.start.saturating_add_signed(delta)..span.end.saturating_add_signed(delta))
+                });
+            tokens.extend(shifted);
+            return (tokens, start..end);
+        }
+    }
+    (tokens, start..source.len())
+}
+
+fn group(source: &str, tokens: &[(Token, Span)]) -> GreenNode {
+    let mut grouper = Grouper::new(source, tokens, 0);
+    for i in 0..tokens.len() {
+        grouper.push(i);
+    }
+    GreenNode::new(NodeKind::Module, grouper.finish())
+}
+
+/// Groups tokens into the children of a module node, one token at a time.
+struct Grouper<'a> {
+    source: &'a str,
+    tokens: &'a [(Token, Span)],
+    /// The finished children.
+    children: Vec<GreenElement>,
+    /// Where the next child starts.
+    offset: usize,
+    item: Option<ItemBuilder>,
+    trivia: Vec<GreenElement>,
+}
+
+impl<'a> Grouper<'a> {
+    /// Start grouping at `offset`, which must be where an item begins.
+    fn new(source: &'a str, tokens: &'a [(Token, Span)], offset: usize) -> Grouper<'a> {
+        Grouper {
+            source,
+            tokens,
+            children: Vec::new(),
+            offset,
+            item: None,
+            trivia: Vec::new(),
+        }
+    }
+
+    fn token(&self, i: usize) -> GreenElement {
+        let (kind, span) = &self.tokens[i];
+        GreenElement::Token(Arc::new(GreenToken::new(kind.clone(), self.source[span.clone()].to_owned())))
+    }
+
+    /// Add the token at `i`. When this finishes an item, returns the offset
+    /// where the item ends.
+    fn push(&mut self, i: usize) -> Option<usize> {
+        let (kind, span) = &self.tokens[i];
+        let trailing = self.item.as_ref().is_some_and(|item| item.trailing);
+        match kind {
+            Token::Whitespace if trailing && !self.source[span.clone()].contains('\n')
+                && matches!(self.tokens.get(i + 1), Some((Token::LineComment, _))) => {
+                let token = self.token(i);
+                self.item.as_mut().unwrap().children.push(token);
+            },
+            Token::LineComment if trailing && self.trivia.is_empty() => {
+                let token = self.token(i);
+                let item = self.item.as_mut().unwrap();
+                item.children.push(token);
+                item.trailing = false;
+            },
+            Token::Whitespace | Token::LineComment => {
+                if let Some(item) = &mut self.item {
+                    item.trailing = false;
+                }
+                let token = self.token(i);
+                self.trivia.push(token);
+            },
+            Token::BlockComment(_) => {
+                if let Some(item) = &mut self.item {
+                    item.trailing = false;
+                }
+                let comment = GreenNode::new(NodeKind::Comment, vec![self.token(i)]);
+                self.trivia.push(GreenElement::Node(Arc::new(comment)));
+            },
+            _ => {
+                let mut finished = None;
+                if !self.item.as_ref().is_some_and(|item| item.continues_with(kind)) {
+                    if let Some(item) = self.item.take().map(ItemBuilder::finish) {
+                        self.offset += item.len();
+                        self.children.push(item);
+                        finished = Some(self.offset);
+                    }
+                    self.item = Some(ItemBuilder::default());
+                }
+                let token = self.token(i);
+                let item = self.item.as_mut().unwrap();
+                item.children.append(&mut self.trivia);
+                item.children.push(token);
+                item.push(kind);
+                return finished;
+            },
+        }
+        None
+    }
+
+    /// The children of the module, once every token has been added.
+    fn finish(mut self) -> Vec<GreenElement> {
+        self.children.extend(self.item.map(ItemBuilder::finish));
+        self.children.append(&mut self.trivia);
+        self.children
+    }
+}
+
+/// The tokens of an item, and enough state to tell where it ends.
+#[derive(Default)]
+struct ItemBuilder {
+    children: Vec<GreenElement>,
+    /// The keyword giving the item's kind, once seen.
+    keyword: Option<Token>,
+    /// Whether only attributes and `pub` have been seen so far.
+    prefix: bool,
+    depth: usize,
+    prev: Option<Token>,
+    ended: bool,
+    /// Whether the item has ended, and trivia on the same line still belongs
+    /// to it.
+    trailing: bool,
+}
+
+impl ItemBuilder {
+    fn continues_with(&self, token: &Token) -> bool {
+        if self.ended {
+            return false;
+        }
+        let after_prefix = self.prefix && self.keyword.is_none();
+        after_prefix || matches!(self.prev, Some(Token::Declare | Token::Pub)) || !begins_item(token, self.depth)
+    }
+
+    fn push(&mut self, token: &Token) {
+        if self.prev.is_none() {
+            self.prefix = matches!(token, Token::Pound | Token::Pub);
+        }
+        if self.keyword.is_none() && self.depth == 0 && begins_item(token, 0) && !matches!(token, Token::Pound | Token::Pub) {
+            self.keyword = Some(token.clone());
+        }
+
+        let braced = matches!(self.keyword, None | Some(Token::Function | Token::Struct));
+        match token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => self.depth += 1,
+            Token::RightParen | Token::RightBracket => self.depth = self.depth.saturating_sub(1),
+            Token::RightBrace => match self.depth {
+                0 | 1 if braced => {
+                    self.depth = 0;
+                    self.ended = true;
+                },
+                _ => self.depth = self.depth.saturating_sub(1),
+            },
+            Token::Semicolon if self.depth == 0 => self.ended = true,
+            _ => {},
+        }
+        self.trailing = self.ended;
+        self.prev = Some(token.clone());
+    }
+
+    fn finish(self) -> GreenElement {
+        GreenElement::Node(Arc::new(GreenNode::new(NodeKind::Item, self.children)))
+    }
+}
+
+/// Whether `token` starts an item. Apart from `in`, which also appears in
+/// `for` loops, these cannot occur inside a function body, so they are
+/// trusted even when braces are unbalanced.
+fn begins_item(token: &Token, depth: usize) -> bool {
+    match token {
+        Token::Pound | Token::Pub | Token::Use | Token::Declare | Token::Const | Token::Uniform | Token::Out
+            | Token::Function | Token::Struct => true,
+        Token::In => depth == 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items(tree: &SyntaxNode) -> Vec<String> {
+        tree.child_nodes()
+            .iter()
+            .filter(|node| node.kind() == NodeKind::Item)
+            .map(SyntaxNode::text)
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_source() {
+        const SOURCES: &[&str] = &[
+            "",
+            "  \n\t",
+            "/// doc\n#[inline]\npub fn main() {\n    let a = b; // note\n}\n",
+            "uniform a: A; /* open /* nested */ still open",
+            "fn main() { let $ = 'x'; }",
+            "} } ; use",
+        ];
+        for source in SOURCES {
+            let tree = parse(source);
+            assert_eq!(tree.text(), *source);
+            assert_eq!(tree.span(), ByteSpan::new(0, source.len()));
+            let joined: String = tree.tokens().iter().map(SyntaxToken::text).collect();
+            assert_eq!(joined, *source);
+        }
+    }
+
+    #[test]
+    fn groups_tokens_into_items() {
+        let tree = parse("// header\n\n/// doc\n#[a] pub uniform a: A; // trailing\nfn main() { a = b; }\nconst B: [f32; 2] = { 1 };\nstruct S { x: X }\n");
+        assert_eq!(items(&tree), vec![
+            "// header\n\n/// doc\n#[a] pub uniform a: A; // trailing",
+            "\nfn main() { a = b; }",
+            "\nconst B: [f32; 2] = { 1 };",
+            "\nstruct S { x: X }",
+        ]);
+        assert!(matches!(tree.children().last(), Some(SyntaxElement::Token(token)) if token.text() == "\n"));
+    }
+
+    #[test]
+    fn groups_malformed_items() {
+        let tree = parse("use a::b\nfn main() { let x = 1;\ndeclare fn f() -> vec4;\n} let y; fn g() {}");
+        assert_eq!(items(&tree), vec![
+            "use a::b",
+            "\nfn main() { let x = 1;",
+            "\ndeclare fn f() -> vec4;",
+            "\n}",
+            " let y;",
+            " fn g() {}",
+        ]);
+    }
+
+    #[test]
+    fn keeps_comments_as_trivia() {
+        let tree = parse("fn main() {\n    /* a */ b(); /* c\n}\n");
+        let item = &tree.child_nodes()[0];
+        assert_eq!(item.text(), "fn main() {\n    /* a */ b();");
+        assert_eq!(item.significant_span(), Some(ByteSpan::new(0, 28)));
+        let comment = &item.child_nodes()[0];
+        assert_eq!(comment.kind(), NodeKind::Comment);
+        assert_eq!(comment.text(), "/* a */");
+        assert!(comment.tokens().iter().all(SyntaxToken::is_trivia));
+        assert_eq!(comment.parent().map(SyntaxNode::kind), Some(NodeKind::Item));
+
+        // an unterminated comment swallows the rest of the source
+        let unterminated = tree.child_nodes().pop().unwrap();
+        assert_eq!(unterminated.kind(), NodeKind::Comment);
+        assert_eq!(unterminated.text(), "/* c\n}\n");
+        assert_eq!(unterminated.parent().map(SyntaxNode::kind), Some(NodeKind::Module));
+    }
+
+    #[test]
+    fn relexes_edited_ranges() {
+        const SOURCE: &str = "uniform a: A;\nfn f() { for i in 0..n { a b } }\n/* c */ struct S { x: X }\n";
+        let edits = [
+            TextEdit::insert(33, ".5".into()),
+            TextEdit::delete(ByteSpan::new(40, 41)),
+            TextEdit::insert(14, "/*".into()),
+            TextEdit::delete(ByteSpan::new(52, 54)),
+            TextEdit::delete(ByteSpan::new(12, 13)),
+            TextEdit::new(ByteSpan::new(0, 7), "const".into()),
+            TextEdit::insert(SOURCE.len(), "fn g() {}".into()),
+            TextEdit::delete(ByteSpan::new(0, SOURCE.len())),
+        ];
+        for edit in &edits {
+            let edited = reparse(&parse(SOURCE), edit);
+            let expected = parse(&edit.apply(SOURCE));
+            assert_eq!(edited.green(), expected.green(), "{:?}", edit);
+        }
+
+        let edited = reparse(&parse(""), &TextEdit::insert(0, "use a;".into()));
+        assert_eq!(edited.text(), "use a;");
+    }
+
+    #[test]
+    fn regroups_items_around_edits() {
+        const SOURCE: &str = "use a; // a\nfn f() { b(); }\n\n/// g\nfn g() {} /* c */\nstruct S { x: X }\n";
+        // every deletion and insertion gives the same tree as parsing again,
+        // even where it moves an item boundary or a trailing comment
+        for start in 0..SOURCE.len() {
+            for end in start..SOURCE.len().min(start + 3) {
+                let edit = TextEdit::delete(ByteSpan::new(start, end));
+                let expected = parse(&edit.apply(SOURCE));
+                assert_eq!(reparse(&parse(SOURCE), &edit).green(), expected.green(), "{:?}", edit);
+            }
+            for text in ["\n", "}", "fn", "/*", "//"] {
+                let edit = TextEdit::insert(start, text.into());
+                let expected = parse(&edit.apply(SOURCE));
+                assert_eq!(reparse(&parse(SOURCE), &edit).green(), expected.green(), "{:?}", edit);
+            }
+        }
+    }
+
+    #[test]
+    fn shares_unchanged_items() {
+        let tree = parse("use a;\nfn f() { a = b; }\nfn g() {}\n");
+        let edited = reparse(&tree, &TextEdit::new(ByteSpan::new(20, 21), "c".into()));
+        assert_eq!(edited.text(), "use a;\nfn f() { a = c; }\nfn g() {}\n");
+
+        let old = tree.child_nodes();
+        let new = edited.child_nodes();
+        assert!(Arc::ptr_eq(old[0].green(), new[0].green()));
+        assert!(!Arc::ptr_eq(old[1].green(), new[1].green()));
+        assert!(Arc::ptr_eq(old[2].green(), new[2].green()));
+        assert_eq!(new[2].span(), ByteSpan::new(24, 34));
+    }
+}
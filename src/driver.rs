@@ -2,6 +2,7 @@ use std::{hash::Hash, sync::Arc};
 
 use crate::{error::Result, session::Session, syntax};
 
+#[derive(Default)]
 pub struct Driver;
 
 impl Driver {
@@ -26,8 +27,12 @@ impl Driver {
         let mut handles = Vec::with_capacity(size_estimate);
 
         for module in includes.modules() {
-            handles.push(session.parse_module(module)?);
+            handles.push(session.parse_module(&module)?);
+            if let Some(path) = session.module_path(&module) {
+                session.resolve_imports(&path)?;
+            }
         }
+        session.bind_env()?;
         session.errors()?;
 
         Ok(handles.into_iter())
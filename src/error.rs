@@ -1,8 +1,9 @@
-use std::{error::Error as StdError, fmt::{self}};
+use std::{error::Error as StdError, fmt::{self}, sync::Arc};
 use thiserror::Error;
 use std::io;
+use std::path::PathBuf;
 
-use crate::{span::{Annotations, Span}, syntax::ParseError};
+use crate::{span::{Annotations, Renderer, SourceFile, Span}, syntax::{ParseError, SyntaxError}};
 
 pub type Result<T> = std::result::Result<T, CompilerError>;
 
@@ -14,11 +15,13 @@ pub enum CompilerError {
     Input(#[from] InputError),
     #[error("There was a problem compiling the shader: {0}")]
     Shader(#[from] ShaderError),
+    #[error("There were problems compiling the shader:\n{0}")]
+    Shaders(#[from] ShaderErrors),
     #[error("Resource unavailable, would block ({0})")]
     WouldBlock(&'static str),
 }
 
-impl<'a> From<io::Error> for CompilerError {
+impl From<io::Error> for CompilerError {
     fn from(error: io::Error) -> Self {
         CompilerError::Input(error.into())
     }
@@ -33,6 +36,14 @@ impl<'a> From<ParseError<'a>> for CompilerError {
     }
 }
 
+impl From<SyntaxError> for CompilerError {
+    fn from(error: SyntaxError) -> Self {
+        CompilerError::Shader(ShaderError {
+            annotations: Annotations::from_error_message(format!("{}", error))
+        })
+    }
+}
+
 impl CompilerError {
     pub fn include_error(item: &str) -> CompilerError {
         CompilerError::Input(InputError::Include(item.to_string()))
@@ -42,6 +53,21 @@ impl CompilerError {
         CompilerError::WouldBlock(resource)
     }
 
+    /// A parse error, located in the file it occurred in.
+    pub fn parse(error: &ParseError, source: &Arc<SourceFile>) -> CompilerError {
+        CompilerError::Shader(ShaderError::parse(error, source))
+    }
+
+    /// Every error found in one pass. A lone error is reported as
+    /// `CompilerError::Shader`.
+    pub fn shaders(mut errors: Vec<ShaderError>) -> CompilerError {
+        if errors.len() == 1 {
+            CompilerError::Shader(errors.remove(0))
+        } else {
+            CompilerError::Shaders(ShaderErrors { errors })
+        }
+    }
+
     pub fn ice(message: String, during: CompilerStage) -> CompilerError {
         CompilerError::Internal(InternalError {
             during,
@@ -55,6 +81,18 @@ impl CompilerError {
 pub enum InputError {
     #[error("{0} is not a valid include")]
     Include(String),
+    #[error("edit {0} does not fall on character boundaries of the source")]
+    Edit(String),
+    #[error("invalid config:\n{0}")]
+    Config(Annotations),
+    #[error("module `{module}` is found at both {} and {}", first.display(), second.display())]
+    DuplicateModule {
+        module: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    #[error("{} cannot be a module, as a name in its module path has a `.`", .0.display())]
+    DottedModule(PathBuf),
     #[error("IO Error: {source}")]
     Io {
         #[from]
@@ -81,11 +119,66 @@ impl fmt::Display for InternalError {
 
 
 #[derive(Error, Debug, Clone)]
-#[error("TODO: an error occurred at {:?}", .annotations.primary)]
 pub struct ShaderError {
     annotations: Annotations,
 }
 
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Renderer::plain().render(&self.annotations))
+    }
+}
+
+impl ShaderError {
+    pub fn new(annotations: Annotations) -> ShaderError {
+        ShaderError {
+            annotations,
+        }
+    }
+
+    /// A parse error, located in the file it occurred in.
+    pub fn parse(error: &ParseError, source: &Arc<SourceFile>) -> ShaderError {
+        ShaderError {
+            annotations: Annotations::error(source, error.span(), error.message().to_string()),
+        }
+    }
+
+    /// A syntax error, located in the file it occurred in.
+    pub fn syntax(error: &SyntaxError, source: &Arc<SourceFile>) -> ShaderError {
+        ShaderError {
+            annotations: Annotations::error(source, error.span(), error.message().to_string()),
+        }
+    }
+
+    pub fn annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+}
+
+/// Several errors found in one pass, such as every syntax error in a file.
+#[derive(Error, Debug, Clone)]
+pub struct ShaderErrors {
+    errors: Vec<ShaderError>,
+}
+
+impl fmt::Display for ShaderErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl ShaderErrors {
+    pub fn errors(&self) -> &[ShaderError] {
+        &self.errors
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum CompilerStage {
     Parsing,
@@ -0,0 +1,884 @@
+//! The canonical source formatter, also run by the `xenofmt` binary.
+//!
+//! A module is printed back from its AST, so the layout of the output only
+//! depends on what the source means: four space indentation, one statement
+//! per line, and lists wrapped one entry per line when they do not fit in
+//! `MAX_WIDTH`. Runs of `use` items are sorted, and grouped by where their
+//! paths start. Comments are taken from the syntax tree and printed before
+//! the item or statement they are found in, or after it when they end its
+//! line in the source.
+
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    cst::{self, NodeKind, SyntaxElement, SyntaxNode},
+    error::{CompilerError, Result, ShaderError},
+    span::{ByteSpan, SourceFile},
+    syntax::{self, *},
+    token::Token,
+};
+
+/// Lines are wrapped to fit within this many characters where possible.
+pub const MAX_WIDTH: usize = 100;
+
+const INDENT: &str = "    ";
+
+/// Format the source of a module.
+pub fn format_source(source: &str) -> Result<String> {
+    format_file(&Arc::new(SourceFile::new("<source>".into(), source.into())))
+}
+
+/// Format a source file. A malformed item cannot be printed canonically, so
+/// this fails with every syntax error in the file.
+pub fn format_file(source: &Arc<SourceFile>) -> Result<String> {
+    let parsed = ParsedModule::new(cst::parse(source.text()));
+    let errors = parsed.errors();
+    if !errors.is_empty() {
+        let errors = errors.iter()
+            .map(|err| ShaderError::syntax(err, source))
+            .collect();
+        return Err(CompilerError::shaders(errors));
+    }
+
+    let mut printer = Printer::new(source.text(), parsed.tree());
+    printer.module(parsed.module());
+    Ok(printer.finish())
+}
+
+/// Whether a source file is already formatted.
+pub fn is_formatted(source: &Arc<SourceFile>) -> Result<bool> {
+    Ok(format_file(source)? == source.text())
+}
+
+struct Comment {
+    span: ByteSpan,
+    text: String,
+    printed: bool,
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    out: String,
+    indent: usize,
+}
+
+/// Items of the same kind which may be written without blank lines between
+/// them.
+#[derive(PartialEq)]
+enum ItemClass {
+    Use,
+    Declare,
+    Global,
+    Other,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, tree: &SyntaxNode) -> Printer<'a> {
+        let mut comments = Vec::new();
+        collect_comments(tree, &mut comments);
+        Printer {
+            source,
+            comments,
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.out.push_str(text);
+        }
+        self.out.push('\n');
+    }
+
+    /// Separate what comes next with a blank line, unless it starts the
+    /// output or a block.
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.indent * INDENT.len() + text.chars().count() <= MAX_WIDTH
+    }
+
+    /// Whether the source has a blank line just before `offset`.
+    fn blank_before(&self, offset: usize) -> bool {
+        let before = &self.source[..offset];
+        let space = &before[before.trim_end().len()..];
+        space.matches('\n').count() >= 2
+    }
+
+    /// Print each comment starting in `range`, on lines of their own.
+    fn comments_in(&mut self, range: Range<usize>) {
+        let ahead: Vec<usize> = (0..self.comments.len())
+            .filter(|&i| !self.comments[i].printed && range.contains(&self.comments[i].span.start))
+            .collect();
+        for i in ahead {
+            if self.blank_before(self.comments[i].span.start) {
+                self.blank_line();
+            }
+            self.comment(i);
+        }
+    }
+
+    fn comments_before(&mut self, offset: usize) {
+        self.comments_in(0..offset);
+    }
+
+    fn comment(&mut self, i: usize) {
+        self.comments[i].printed = true;
+        let text = std::mem::take(&mut self.comments[i].text);
+        for (n, line) in text.lines().enumerate() {
+            if n == 0 {
+                self.line(line.trim_end());
+            } else {
+                // later lines of a block comment are kept as written
+                self.out.push_str(line.trim_end());
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// The comments after `end` on the same line of the source, with only
+    /// separators between them.
+    fn trailing_comments(&self, mut end: usize) -> Vec<usize> {
+        let mut trailing = Vec::new();
+        for (i, comment) in self.comments.iter().enumerate() {
+            if comment.printed || comment.span.start < end || comment.text.contains('\n') {
+                continue;
+            }
+            let between = &self.source[end..comment.span.start];
+            if !between.chars().all(|c| c == ' ' || c == '\t' || c == ',' || c == ';') {
+                break;
+            }
+            trailing.push(i);
+            end = comment.span.end;
+        }
+        trailing
+    }
+
+    /// Print the comments ending the line of the source at `end` at the end
+    /// of the last line printed.
+    fn trailing(&mut self, end: usize) {
+        for i in self.trailing_comments(end) {
+            self.append_comment(i);
+        }
+    }
+
+    fn append_comment(&mut self, i: usize) {
+        self.comments[i].printed = true;
+        self.out.pop();
+        self.out.push(' ');
+        self.out.push_str(self.comments[i].text.trim_end());
+        self.out.push('\n');
+    }
+
+    fn has_comments(&self, span: ByteSpan) -> bool {
+        self.comments.iter().any(|comment| !comment.printed && span.range().contains(&comment.span.start))
+    }
+
+    fn module(&mut self, module: &Module) {
+        let items = module.items();
+        let mut prev: Option<&Item> = None;
+        let mut i = 0;
+        while i < items.len() {
+            let item = &items[i];
+            if let Some(prev) = prev {
+                if class(prev) != class(item) || class(item) == ItemClass::Other {
+                    self.blank_line();
+                }
+            }
+            self.comments_before(item.span().start);
+            if self.blank_before(item.span().start) {
+                self.blank_line();
+            }
+
+            if class(item) == ItemClass::Use {
+                let run = items[i..].iter().take_while(|item| class(item) == ItemClass::Use).count();
+                self.uses(&items[i..i + run]);
+                prev = Some(&items[i + run - 1]);
+                i += run;
+            } else {
+                self.item(item);
+                prev = Some(item);
+                i += 1;
+            }
+        }
+        self.comments_before(self.source.len() + 1);
+    }
+
+    /// Print a run of `use` items, sorted and grouped. Comments before a
+    /// `use` move with it.
+    fn uses(&mut self, items: &[Item]) {
+        let mut entries = Vec::new();
+        let mut start = items[0].span().start;
+        let mut claimed = Vec::new();
+        for item in items {
+            let leading: Vec<usize> = (0..self.comments.len())
+                .filter(|i| !self.comments[*i].printed && !claimed.contains(i))
+                .filter(|&i| (start..item.span().end).contains(&self.comments[i].span.start))
+                .collect();
+            let trailing = self.trailing_comments(item.span().end);
+            claimed.extend(&leading);
+            claimed.extend(&trailing);
+            start = item.span().end;
+
+            let (group, text) = match item.kind() {
+                ItemKind::Use(item) => (root_order(item.root()), use_text(item)),
+                _ => (0, String::new()),
+            };
+            entries.push((group, text, item, leading, trailing));
+        }
+        entries.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+        let mut group = None;
+        for (this_group, _, item, leading, trailing) in entries {
+            if group.is_some_and(|group| group != this_group) {
+                self.blank_line();
+            }
+            group = Some(this_group);
+            for i in leading {
+                self.comment(i);
+            }
+            self.item_text(item);
+            for i in trailing {
+                self.append_comment(i);
+            }
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        let head_end = match item.kind() {
+            ItemKind::Function(function) => function.body().span().start,
+            ItemKind::Struct(item) => item.name().span().end,
+            _ => item.span().end,
+        };
+        self.comments_before(head_end);
+        self.item_text(item);
+        self.trailing(item.span().end);
+    }
+
+    fn item_text(&mut self, item: &Item) {
+        for attribute in item.attributes() {
+            self.line(&attribute_text(attribute));
+        }
+        let visibility = match item.visibility() {
+            Visibility::Public => "pub ",
+            Visibility::Private => "",
+        };
+
+        match item.kind() {
+            ItemKind::Use(item) => self.use_item(visibility, item),
+            ItemKind::Declare(Declare::Function(function)) => {
+                let head = format!("{}declare fn {}", visibility, function.name().str());
+                let ret = return_text(function.return_type());
+                self.signature(&head, function.arguments(), &ret, ";");
+            },
+            ItemKind::Declare(Declare::Type(declare)) => {
+                self.line(&format!("{}declare type {};", visibility, declare.name().str()));
+            },
+            ItemKind::Declare(Declare::Const(declare)) => {
+                self.line(&format!("{}declare const {}: {};", visibility, declare.name().str(), type_text(declare.type_name())));
+            },
+            ItemKind::Global(global) => {
+                let qualifier = match global.qualifier() {
+                    GlobalQualifier::In => "in",
+                    GlobalQualifier::Out => "out",
+                    GlobalQualifier::Uniform => "uniform",
+                    GlobalQualifier::Const => "const",
+                };
+                let head = format!("{}{} {}: {}", visibility, qualifier, global.identifier().str(), type_text(global.definition()));
+                match global.initializer() {
+                    Some(value) => self.expr(&format!("{} = ", head), value, ";"),
+                    None => self.line(&format!("{};", head)),
+                }
+            },
+            ItemKind::Function(function) => {
+                let head = format!("{}fn {}", visibility, function.name().str());
+                let ret = function.return_type().map(return_text).unwrap_or_default();
+                let body = function.body();
+                if self.is_empty(body) {
+                    self.signature(&head, function.arguments(), &ret, " {}");
+                } else {
+                    self.signature(&head, function.arguments(), &ret, " {");
+                    self.block_body(body, "");
+                }
+            },
+            ItemKind::Struct(item) => {
+                self.line(&format!("{}struct {} {{", visibility, item.name().str()));
+                self.indent += 1;
+                for (name, definition) in item.fields() {
+                    self.comments_before(name.span().start);
+                    self.line(&format!("{}: {},", name.str(), type_text(definition)));
+                    self.trailing(definition.span().end);
+                }
+                self.comments_before(item.span().end);
+                self.indent -= 1;
+                self.line("}");
+            },
+            ItemKind::Error => {
+                let text = self.source[item.span().range()].to_owned();
+                self.line(&text);
+            },
+        }
+    }
+
+    fn use_item(&mut self, visibility: &str, item: &Use) {
+        let text = format!("{}{};", visibility, use_text(item));
+        let tree = item.tree();
+        match tree.kind() {
+            UseTreeKind::Group(trees) if !self.fits(&text) => {
+                let mut path: Vec<&str> = tree.path().iter().map(Identifier::str).collect();
+                path.push("{");
+                self.line(&format!("{}use {}{}", visibility, root_text(item.root()), path.join("::")));
+                self.indent += 1;
+                for tree in sorted_trees(trees) {
+                    self.line(&format!("{},", tree));
+                }
+                self.indent -= 1;
+                self.line("};");
+            },
+            _ => self.line(&text),
+        }
+    }
+
+    /// Print a function signature, wrapping its arguments one per line if
+    /// it is too long.
+    fn signature(&mut self, head: &str, arguments: &Arguments, ret: &str, suffix: &str) {
+        let texts: Vec<String> = arguments.iter()
+            .map(|(name, definition)| format!("{}: {}", name.str(), type_text(definition)))
+            .collect();
+        let flat = format!("{}({}){}{}", head, texts.join(", "), ret, suffix);
+        if texts.is_empty() || self.fits(&flat) {
+            self.line(&flat);
+            return;
+        }
+        self.line(&format!("{}(", head));
+        self.indent += 1;
+        for text in texts {
+            self.line(&format!("{},", text));
+        }
+        self.indent -= 1;
+        self.line(&format!("){}{}", ret, suffix));
+    }
+
+    fn is_empty(&self, block: &Block) -> bool {
+        block.statements().is_empty() && block.tail().is_none() && !self.has_comments(block.span())
+    }
+
+    /// Print `head` followed by a block, then `suffix`.
+    fn block(&mut self, head: &str, block: &Block, suffix: &str) {
+        if self.is_empty(block) {
+            self.line(&format!("{}{{}}{}", head, suffix));
+        } else {
+            self.line(&format!("{}{{", head));
+            self.block_body(block, suffix);
+        }
+    }
+
+    /// Print the contents of a block after the line with its opening brace,
+    /// then the closing brace followed by `suffix`.
+    fn block_body(&mut self, block: &Block, suffix: &str) {
+        self.trailing(block.span().start + 1);
+        self.indent += 1;
+        self.block_contents(block);
+        self.indent -= 1;
+        self.line(&format!("}}{}", suffix));
+    }
+
+    /// Print the statements of a block and the comments in it.
+    fn block_contents(&mut self, block: &Block) {
+        for statement in block.statements() {
+            self.statement(statement);
+        }
+        if let Some(tail) = block.tail() {
+            self.comments_before(tail.span().start);
+            if self.blank_before(tail.span().start) {
+                self.blank_line();
+            }
+            match tail.kind() {
+                ExprKind::If(if_expr) => self.if_lines("", if_expr, ""),
+                _ => self.expr("", tail, ""),
+            }
+            self.trailing(tail.span().end);
+        }
+        self.comments_before(block.span().end.saturating_sub(1));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let head_end = match statement.kind() {
+            StatementKind::Block(block) => block.span().start,
+            StatementKind::For(for_loop) => for_loop.body().span().start,
+            StatementKind::While(while_loop) => while_loop.body().span().start,
+            StatementKind::Expr(expr) => match expr.kind() {
+                ExprKind::If(if_expr) => if_expr.then().span().start,
+                _ => statement.span().end,
+            },
+            _ => statement.span().end,
+        };
+        self.comments_before(head_end);
+        if self.blank_before(statement.span().start) {
+            self.blank_line();
+        }
+
+        match statement.kind() {
+            StatementKind::Let(binding) => {
+                let head = let_head(binding);
+                match binding.expression() {
+                    Some(value) => self.expr(&format!("{} = ", head), value, ";"),
+                    None => self.line(&format!("{};", head)),
+                }
+            },
+            StatementKind::Assignment(assignment) => {
+                let head = format!("{} {} ", flat(assignment.target()), assignment_operator(assignment));
+                self.expr(&head, assignment.expression(), ";");
+            },
+            StatementKind::Block(block) => self.block("", block, ""),
+            StatementKind::Expr(expr) => match expr.kind() {
+                ExprKind::If(if_expr) => self.if_lines("", if_expr, ""),
+                _ => self.expr("", expr, ";"),
+            },
+            StatementKind::For(for_loop) => {
+                let head = format!("for {} in {} ", for_loop.binding().str(), range_text(for_loop.range()));
+                self.block(&head, for_loop.body(), "");
+            },
+            StatementKind::While(while_loop) => {
+                let head = format!("while {} ", flat(while_loop.condition()));
+                self.block(&head, while_loop.body(), "");
+            },
+            StatementKind::Return(Some(value)) => self.expr("return ", value, ";"),
+            StatementKind::Return(None) => self.line("return;"),
+            StatementKind::Break => self.line("break;"),
+            StatementKind::Continue => self.line("continue;"),
+        }
+        self.trailing(statement.span().end);
+    }
+
+    /// Print an `if` and its `else` branches over several lines.
+    fn if_lines(&mut self, prefix: &str, if_expr: &If, suffix: &str) {
+        let mut line = format!("{}if {} ", prefix, flat(if_expr.condition()));
+        let mut current = if_expr;
+        loop {
+            line = self.open_block(&line, current.then());
+            match current.otherwise() {
+                None => break,
+                Some(Else::If(next)) => {
+                    line = format!("{} else if {} ", line, flat(next.condition()));
+                    current = next;
+                },
+                Some(Else::Block(block)) => {
+                    line = self.open_block(&format!("{} else ", line), block);
+                    break;
+                },
+            }
+        }
+        self.line(&format!("{}{}", line, suffix));
+    }
+
+    /// Print `head` followed by a block, except for the block's last line,
+    /// which is returned so that more can follow it.
+    fn open_block(&mut self, head: &str, block: &Block) -> String {
+        if self.is_empty(block) {
+            return format!("{}{{}}", head);
+        }
+        self.line(&format!("{}{{", head));
+        self.trailing(block.span().start + 1);
+        self.indent += 1;
+        self.block_contents(block);
+        self.indent -= 1;
+        "}".to_owned()
+    }
+
+    /// Print an expression between `prefix` and `suffix`, on one line if it
+    /// fits, or else broken over several.
+    fn expr(&mut self, prefix: &str, expr: &Expr, suffix: &str) {
+        let text = format!("{}{}{}", prefix, flat(expr), suffix);
+        if !needs_lines(expr) && self.fits(&text) {
+            self.line(&text);
+            return;
+        }
+
+        match expr.kind() {
+            ExprKind::If(if_expr) => self.if_lines(prefix, if_expr, suffix),
+            ExprKind::Call(callee, arguments) if !arguments.is_empty() => {
+                self.expr(prefix, callee, "(");
+                self.indent += 1;
+                for argument in arguments {
+                    self.expr("", argument, ",");
+                }
+                self.indent -= 1;
+                self.line(&format!("){}", suffix));
+            },
+            ExprKind::Struct(name, fields) => {
+                self.line(&format!("{}{} {{", prefix, name.str()));
+                self.indent += 1;
+                for (field, value) in fields {
+                    self.expr(&format!("{}: ", field.str()), value, ",");
+                }
+                self.indent -= 1;
+                self.line(&format!("}}{}", suffix));
+            },
+            ExprKind::Binary(op, left, right) => {
+                self.expr(prefix, left, "");
+                self.indent += 1;
+                self.expr(&format!("{} ", op.symbol()), right, suffix);
+                self.indent -= 1;
+            },
+            ExprKind::Group(inner) => self.expr(&format!("{}(", prefix), inner, &format!("){}", suffix)),
+            ExprKind::Unary(op, operand) => self.expr(&format!("{}{}", prefix, op.symbol()), operand, suffix),
+            ExprKind::Field(base, field) => self.expr(prefix, base, &format!(".{}{}", field.str(), suffix)),
+            ExprKind::Index(base, index) if needs_lines(index) => {
+                self.expr(prefix, base, "[");
+                self.indent += 1;
+                self.expr("", index, "");
+                self.indent -= 1;
+                self.line(&format!("]{}", suffix));
+            },
+            ExprKind::Index(base, index) => self.expr(prefix, base, &format!("[{}]{}", flat(index), suffix)),
+            _ => self.line(&text),
+        }
+    }
+}
+
+fn collect_comments(node: &SyntaxNode, comments: &mut Vec<Comment>) {
+    for child in node.children() {
+        match child {
+            SyntaxElement::Node(node) if node.kind() == NodeKind::Comment => comments.push(Comment {
+                span: node.span(),
+                text: node.text(),
+                printed: false,
+            }),
+            SyntaxElement::Node(node) => collect_comments(&node, comments),
+            SyntaxElement::Token(token) if *token.kind() == Token::LineComment => comments.push(Comment {
+                span: token.span(),
+                text: token.text().to_owned(),
+                printed: false,
+            }),
+            SyntaxElement::Token(_) => {},
+        }
+    }
+}
+
+fn class(item: &Item) -> ItemClass {
+    match item.kind() {
+        ItemKind::Use(_) => ItemClass::Use,
+        ItemKind::Declare(_) => ItemClass::Declare,
+        ItemKind::Global(_) => ItemClass::Global,
+        _ => ItemClass::Other,
+    }
+}
+
+/// `use` items from other modules come first, then the crate, then parents.
+fn root_order(root: PathRoot) -> usize {
+    match root {
+        PathRoot::Source => 0,
+        PathRoot::Crate => 1,
+        PathRoot::Super(_) => 2,
+    }
+}
+
+fn root_text(root: PathRoot) -> String {
+    match root {
+        PathRoot::Source => String::new(),
+        PathRoot::Crate => "crate::".to_owned(),
+        PathRoot::Super(depth) => "super::".repeat(depth),
+    }
+}
+
+fn use_text(item: &Use) -> String {
+    format!("use {}{}", root_text(item.root()), use_tree_text(item.tree()))
+}
+
+fn use_tree_text(tree: &UseTree) -> String {
+    let mut parts: Vec<String> = tree.path().iter().map(|name| name.str().to_owned()).collect();
+    match tree.kind() {
+        UseTreeKind::Simple(None) => {},
+        UseTreeKind::Simple(Some(alias)) => {
+            let last = parts.pop().unwrap_or_default();
+            parts.push(format!("{} as {}", last, alias.str()));
+        },
+        UseTreeKind::Glob => parts.push("*".to_owned()),
+        UseTreeKind::Group(trees) => parts.push(format!("{{{}}}", sorted_trees(trees).join(", "))),
+    }
+    parts.join("::")
+}
+
+fn sorted_trees(trees: &[UseTree]) -> Vec<String> {
+    let mut texts: Vec<String> = trees.iter().map(use_tree_text).collect();
+    texts.sort();
+    texts
+}
+
+fn attribute_text(attribute: &Attribute) -> String {
+    match attribute.arguments() {
+        Some(arguments) => format!("#[{}({})]", attribute.name().str(), list(arguments)),
+        None => format!("#[{}]", attribute.name().str()),
+    }
+}
+
+/// The ` -> T` of a signature, which is left out for the unit type.
+fn return_text(type_name: &TypeName) -> String {
+    match type_name.kind() {
+        TypeNameKind::Tuple(types) if types.is_empty() => String::new(),
+        _ => format!(" -> {}", type_text(type_name)),
+    }
+}
+
+fn type_text(type_name: &TypeName) -> String {
+    match type_name.kind() {
+        TypeNameKind::Identifier(name) => name.str().to_owned(),
+        TypeNameKind::Tuple(types) if types.len() == 1 => format!("({},)", type_text(&types[0])),
+        TypeNameKind::Tuple(types) => {
+            let types: Vec<String> = types.iter().map(type_text).collect();
+            format!("({})", types.join(", "))
+        },
+        TypeNameKind::Literal(literal) => literal.text().to_owned(),
+        TypeNameKind::Array(element, Some(size)) => format!("[{}; {}]", type_text(element), flat(size)),
+        TypeNameKind::Array(element, None) => format!("[{}]", type_text(element)),
+        TypeNameKind::Generic(name, arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(type_text).collect();
+            format!("{}<{}>", name.str(), arguments.join(", "))
+        },
+    }
+}
+
+fn let_head(binding: &Let) -> String {
+    let mut head = String::from("let ");
+    if binding.mutable() {
+        head.push_str("mut ");
+    }
+    head.push_str(binding.binding().str());
+    if let Some(type_name) = binding.type_name() {
+        head.push_str(": ");
+        head.push_str(&type_text(type_name));
+    }
+    head
+}
+
+fn assignment_operator(assignment: &Assignment) -> String {
+    match assignment.operator() {
+        Some(op) => format!("{}=", op.symbol()),
+        None => "=".to_owned(),
+    }
+}
+
+fn range_text(range: &syntax::Range) -> String {
+    let op = if range.inclusive() { "..=" } else { ".." };
+    format!("{}{}{}", flat(range.start()), op, flat(range.end()))
+}
+
+fn list(exprs: &[Expr]) -> String {
+    let texts: Vec<String> = exprs.iter().map(flat).collect();
+    texts.join(", ")
+}
+
+/// An expression on a single line.
+fn flat(expr: &Expr) -> String {
+    match expr.kind() {
+        ExprKind::Literal(literal) => literal.text().to_owned(),
+        ExprKind::Identifier(name) => name.str().to_owned(),
+        ExprKind::Unary(op, operand) => format!("{}{}", op.symbol(), flat(operand)),
+        ExprKind::Binary(op, left, right) => format!("{} {} {}", flat(left), op.symbol(), flat(right)),
+        ExprKind::Group(inner) => format!("({})", flat(inner)),
+        ExprKind::Call(callee, arguments) => format!("{}({})", flat(callee), list(arguments)),
+        ExprKind::Field(base, field) => format!("{}.{}", flat(base), field.str()),
+        ExprKind::Index(base, index) => format!("{}[{}]", flat(base), flat(index)),
+        ExprKind::Struct(name, fields) => {
+            let fields: Vec<String> = fields.iter()
+                .map(|(field, value)| format!("{}: {}", field.str(), flat(value)))
+                .collect();
+            format!("{} {{ {} }}", name.str(), fields.join(", "))
+        },
+        ExprKind::If(if_expr) => flat_if(if_expr),
+    }
+}
+
+fn flat_if(if_expr: &If) -> String {
+    let mut text = format!("if {} {}", flat(if_expr.condition()), flat_block(if_expr.then()));
+    match if_expr.otherwise() {
+        Some(Else::If(next)) => text.push_str(&format!(" else {}", flat_if(next))),
+        Some(Else::Block(block)) => text.push_str(&format!(" else {}", flat_block(block))),
+        None => {},
+    }
+    text
+}
+
+fn flat_block(block: &Block) -> String {
+    let mut parts: Vec<String> = block.statements().iter().map(flat_statement).collect();
+    parts.extend(block.tail().map(flat));
+    if parts.is_empty() {
+        "{}".to_owned()
+    } else {
+        format!("{{ {} }}", parts.join(" "))
+    }
+}
+
+fn flat_statement(statement: &Statement) -> String {
+    match statement.kind() {
+        StatementKind::Let(binding) => match binding.expression() {
+            Some(value) => format!("{} = {};", let_head(binding), flat(value)),
+            None => format!("{};", let_head(binding)),
+        },
+        StatementKind::Assignment(assignment) => {
+            format!("{} {} {};", flat(assignment.target()), assignment_operator(assignment), flat(assignment.expression()))
+        },
+        StatementKind::Block(block) => flat_block(block),
+        StatementKind::Expr(expr) if matches!(expr.kind(), ExprKind::If(_)) => flat(expr),
+        StatementKind::Expr(expr) => format!("{};", flat(expr)),
+        StatementKind::For(for_loop) => {
+            format!("for {} in {} {}", for_loop.binding().str(), range_text(for_loop.range()), flat_block(for_loop.body()))
+        },
+        StatementKind::While(while_loop) => format!("while {} {}", flat(while_loop.condition()), flat_block(while_loop.body())),
+        StatementKind::Return(Some(value)) => format!("return {};", flat(value)),
+        StatementKind::Return(None) => "return;".to_owned(),
+        StatementKind::Break => "break;".to_owned(),
+        StatementKind::Continue => "continue;".to_owned(),
+    }
+}
+
+/// Whether an expression holds a block with statements, which is only
+/// printed over several lines.
+fn needs_lines(expr: &Expr) -> bool {
+    match expr.kind() {
+        ExprKind::Literal(_) | ExprKind::Identifier(_) => false,
+        ExprKind::Unary(_, inner) | ExprKind::Group(inner) | ExprKind::Field(inner, _) => needs_lines(inner),
+        ExprKind::Binary(_, left, right) | ExprKind::Index(left, right) => needs_lines(left) || needs_lines(right),
+        ExprKind::Call(callee, arguments) => needs_lines(callee) || arguments.iter().any(needs_lines),
+        ExprKind::Struct(_, fields) => fields.iter().any(|(_, value)| needs_lines(value)),
+        ExprKind::If(if_expr) => if_needs_lines(if_expr),
+    }
+}
+
+fn if_needs_lines(if_expr: &If) -> bool {
+    let block_needs_lines = |block: &Block| !block.statements().is_empty() || block.tail().is_some_and(needs_lines);
+    let otherwise = match if_expr.otherwise() {
+        Some(Else::If(next)) => if_needs_lines(next),
+        Some(Else::Block(block)) => block_needs_lines(block),
+        None => false,
+    };
+    needs_lines(if_expr.condition()) || block_needs_lines(if_expr.then()) || otherwise
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_modules() {
+        let source = "// shading helpers\nuse super::light;\nuse crate::math::{mix,clamp};\nuse std::vec4;\n\
+            uniform  scene:Scene;\nuniform material: Material;\n\
+            fn   shade(a:vec4,b : vec4)->vec4{let  a = 1; // one\n\
+            if a>b {a} else {b}}\n\
+            struct S{x:f32,y:f32}\nfn empty( ) { }\n";
+        assert_eq!(format_source(source).unwrap(), "\
+// shading helpers
+use std::vec4;
+
+use crate::math::{clamp, mix};
+
+use super::light;
+
+uniform scene: Scene;
+uniform material: Material;
+
+fn shade(a: vec4, b: vec4) -> vec4 {
+    let a = 1; // one
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+struct S {
+    x: f32,
+    y: f32,
+}
+
+fn empty() {}
+");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let source = "use b::c; // c\n// a\nuse a;\n\n\n/* block\n   comment */\nfn f(){ \
+            x += g(1, 2); /* inner */ while x < 10 { x = x * 2; }\n\n for i in 0..=3 { if i == 1 { break; } } return; }\n";
+        let once = format_source(source).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+        assert!(is_formatted(&Arc::new(SourceFile::new("f".into(), once))).unwrap());
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let arguments = "first_argument: vec4, second_argument: vec4, third_argument: vec4, fourth_argument: vec4, fifth_argument: vec4";
+        let source = format!("fn f({}) {{ g(first_argument, second_argument, third_argument, fourth_argument, fifth_argument, sixth_argument); }}", arguments);
+        assert_eq!(format_source(&source).unwrap(), "\
+fn f(
+    first_argument: vec4,
+    second_argument: vec4,
+    third_argument: vec4,
+    fourth_argument: vec4,
+    fifth_argument: vec4,
+) {
+    g(
+        first_argument,
+        second_argument,
+        third_argument,
+        fourth_argument,
+        fifth_argument,
+        sixth_argument,
+    );
+}
+");
+    }
+
+    #[test]
+    fn formats_empty_bodies_and_returns() {
+        let source = "declare fn discard() -> ();\nfn g() -> () {}\nfn f() { if a {} else { b(); } if c { d(); } else if e {} }\n";
+        assert_eq!(format_source(source).unwrap(), "\
+declare fn discard();
+
+fn g() {}
+
+fn f() {
+    if a {} else {
+        b();
+    }
+    if c {
+        d();
+    } else if e {}
+}
+");
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source = "// header\n\nuse a; // first\n/* before b */ use b;\n\
+            fn f(/* args */) { // open\n  let x = 1 + /* inside */ 2;\n  // last\n}\n// end\n";
+        let formatted = format_source(source).unwrap();
+        for comment in ["// header", "// first", "/* before b */", "/* args */", "// open", "/* inside */", "// last", "// end"] {
+            assert!(formatted.contains(comment), "{} missing from\n{}", comment, formatted);
+        }
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn rejects_malformed_modules() {
+        assert!(matches!(format_source("fn f( {"), Err(CompilerError::Shader(_))));
+    }
+}
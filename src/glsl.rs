@@ -0,0 +1,60 @@
+use std::borrow::Cow;
+
+/// Prefix of every mangled identifier. User identifiers may not begin with
+/// it, so mangled names never collide with names written in source.
+pub const MANGLE_PREFIX: &str = "xv_";
+
+/// Keywords, reserved words and built-in type names of GLSL which are
+/// otherwise valid identifiers in shader source.
+pub const KEYWORDS: &[&str] = &[
+    "active", "asm", "atomic_uint", "attribute", "bool", "buffer", "bvec2",
+    "bvec3", "bvec4", "case", "cast", "centroid", "class", "coherent",
+    "common", "default", "discard", "do", "double", "dvec2", "dvec3", "dvec4",
+    "extern", "external", "filter", "fixed", "flat", "float", "goto", "half",
+    "highp", "hvec2", "hvec3", "hvec4", "inline", "inout", "input", "int",
+    "interface", "invariant", "isampler2D", "isampler3D", "ivec2", "ivec3",
+    "ivec4", "layout", "long", "lowp", "mat2", "mat3", "mat4", "mediump",
+    "namespace", "noinline", "noperspective", "output", "partition", "patch",
+    "precise", "precision", "public", "readonly", "resource", "restrict",
+    "sample", "sampler1D", "sampler2D", "sampler3D", "samplerCube", "shared",
+    "short", "sizeof", "smooth", "subroutine", "superp", "switch", "template",
+    "this", "typedef", "uint", "union", "unsigned", "using", "usampler2D",
+    "usampler3D", "uvec2", "uvec3", "uvec4", "varying", "vec2", "vec3",
+    "vec4", "void", "volatile", "writeonly",
+];
+
+/// Map a source identifier to one that is valid in GLSL.
+///
+/// Identifiers which clash with a GLSL keyword gain the mangle prefix, and
+/// identifiers outside of ASCII (which GLSL does not allow) are spelled out
+/// as the hex of their UTF-8 bytes.
+pub fn mangle(identifier: &str) -> Cow<'_, str> {
+    if !identifier.is_ascii() {
+        let hex: String = identifier.bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Cow::Owned(format!("{}u{}", MANGLE_PREFIX, hex))
+    } else if KEYWORDS.contains(&identifier) {
+        Cow::Owned(format!("{}{}", MANGLE_PREFIX, identifier))
+    } else {
+        Cow::Borrowed(identifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mangles_keywords() {
+        assert_eq!(mangle("base_color"), "base_color");
+        assert_eq!(mangle("input"), "xv_input");
+        assert_eq!(mangle("sampler2D"), "xv_sampler2D");
+    }
+
+    #[test]
+    fn mangles_unicode() {
+        assert_eq!(mangle("größe"), "xv_u6772c3b6c39f65");
+        assert!(mangle("é").is_ascii());
+    }
+}
@@ -1,25 +1,47 @@
 use std::{collections::HashMap, sync::Arc};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Hir {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_nodes"))]
     nodes: HashMap<NodeId, Arc<Node>>,
 }
 
+impl Hir {
+    pub fn node(&self, id: &NodeId) -> Option<&Arc<Node>> {
+        self.nodes.get(id)
+    }
+}
+
+/// Serialize nodes as a list ordered by ID, so the output does not depend
+/// on the order of the map.
+#[cfg(feature = "serde")]
+fn serialize_nodes<S: serde::Serializer>(nodes: &HashMap<NodeId, Arc<Node>>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut nodes: Vec<&Node> = nodes.values().map(|node| &**node).collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    serializer.collect_seq(nodes)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NodeId(u64);
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Node {
     pub id: NodeId,
     pub kind: NodeKind,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum NodeKind {
     Function(Function),
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Function {
 
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Module {
     items: Vec<NodeId>,
 }
@@ -30,12 +52,18 @@ impl Module {
             items: Vec::new(),
         }
     }
+
+    pub fn items(&self) -> &[NodeId] {
+        &self.items
+    }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Global {
 
 }
 
+#[allow(dead_code)]
 impl Global {
     fn type_identifier(&self) -> &str {
         "i32"
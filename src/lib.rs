@@ -1,4 +1,6 @@
 pub mod syntax;
+pub mod cst;
+pub mod format;
 pub mod token;
 pub mod config;
 pub mod compiler;
@@ -9,3 +11,4 @@ pub mod driver;
 pub mod session;
 pub mod error;
 pub mod span;
+pub mod glsl;
@@ -5,7 +5,7 @@ pub struct Linker {
 }
 
 impl Linker {
-    pub fn deps(&self, function: &hir::Function) -> Deps {
+    pub fn deps(&self, _function: &hir::Function) -> Deps {
         Deps {}
     }
 }
@@ -1,41 +1,124 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::hash::Hash;
 use std::path::{Component, Path};
-use std::sync::{Arc, RwLock, TryLockError};
+use std::sync::{Arc, RwLock, RwLockWriteGuard, TryLockError};
 use std::{collections::HashMap, path::PathBuf};
-use std::fs::{read_dir, read_to_string};
 use std::io;
 
 use crate::ast::{self, parse_path};
-use crate::syntax::parse_module;
+use crate::span::SourceFile;
+use crate::cst::{self, TextEdit};
+use crate::syntax::{ItemChanges, ParsedModule, SyntaxError};
 use crate::{config, syntax};
-use crate::error::{CompilerError, CompilerStage};
+use crate::error::{CompilerError, CompilerStage, InputError, ShaderError};
 use crate::{error::Result};
 
+mod env;
+mod ignore;
+mod resolve;
+mod vfs;
+pub use env::ConstValue;
+pub use ignore::IGNORE_FILE;
+use ignore::IgnoreRules;
+pub use resolve::{Ambiguity, Imports, Resolved};
+pub use vfs::{DirEntry, FileKind, FileSystem, MemoryFileSystem, OsFileSystem};
+
 pub struct Session {
+    fs: Arc<dyn FileSystem>,
     source_store: SourceStore,
-    parsed: RwLock<HashMap<PathBuf, Arc<syntax::Module>>>,
+    /// The text of modules given as `Input::Source`, by their stand-in file.
+    memory: HashMap<PathBuf, String>,
+    sources: RwLock<HashMap<PathBuf, Arc<SourceFile>>>,
+    parsed: RwLock<HashMap<PathBuf, ParsedModule>>,
+    imports: RwLock<HashMap<ast::Path, Arc<Imports>>>,
+    /// Values for `declare const` items, by name.
+    env: BTreeMap<String, config::EnvVar<String>>,
+    /// Values for `declare const` items which are not given one in `env`.
+    /// Unlike those in `env`, they need not be taken by any item.
+    defaults: BTreeMap<String, config::EnvVar<String>>,
+    /// The values bound to each `declare const` item, by file and name.
+    bound: RwLock<HashMap<(PathBuf, String), ConstValue>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
 }
 
 impl Session {
     pub fn new() -> Session {
+        Session::with_file_system(Arc::new(OsFileSystem))
+    }
+
+    /// Begin a session which reads sources through `fs`.
+    pub fn with_file_system(fs: Arc<dyn FileSystem>) -> Session {
         let source_store = SourceStore::new();
+        let memory = HashMap::new();
+        let sources = RwLock::new(HashMap::new());
         let parsed = RwLock::new(HashMap::new());
+        let imports = RwLock::new(HashMap::new());
+        let env = BTreeMap::new();
+        let defaults = BTreeMap::new();
+        let bound = RwLock::new(HashMap::new());
         Session {
+            fs,
             source_store,
+            memory,
+            sources,
             parsed,
+            imports,
+            env,
+            defaults,
+            bound,
         }
     }
 
-    pub fn register_input(&mut self, input: &config::Input) -> io::Result<()> {
+    /// Only files with this extension are modules. Applies to inputs
+    /// registered afterwards.
+    pub fn set_extension(&mut self, extension: &str) {
+        self.source_store.extension = extension.to_owned();
+    }
+
+    /// Read sources through `fs` from now on. Sources already read are
+    /// kept.
+    pub fn set_file_system(&mut self, fs: Arc<dyn FileSystem>) {
+        self.fs = fs;
+    }
+
+    pub fn register_input(&mut self, input: &config::Input) -> Result<()> {
         match input {
             config::Input::Path(path) => {
-                self.source_store.discover_tree(path)?;
+                self.source_store.discover_tree(&*self.fs, path)?;
+            },
+            config::Input::Source { module, text } => {
+                let file = self.source_store.add_source(source_module_path(module)?)?;
+                self.memory.insert(file.clone(), text.clone());
+                // forget what was parsed from an earlier text of the module
+                if let Ok(sources) = self.sources.get_mut() {
+                    sources.remove(&file);
+                }
+                if let Ok(parsed) = self.parsed.get_mut() {
+                    parsed.remove(&file);
+                }
+                if let Ok(imports) = self.imports.get_mut() {
+                    imports.clear();
+                }
+                if let Ok(bound) = self.bound.get_mut() {
+                    bound.clear();
+                }
             },
         }
         Ok(())
     }
 
+    /// The module files under `root`, as registering it as an input would
+    /// find them: files with the session's extension which the root's
+    /// ignore file does not exclude.
+    pub fn discover_files<P: AsRef<Path>>(&self, root: P) -> Result<Vec<PathBuf>> {
+        self.source_store.discover_files(&*self.fs, root.as_ref())
+    }
+
     pub fn parse_references<'a: 'i, 'i, S: AsRef<str> + Eq + Hash + 'i, I: Iterator<Item=S>>(&'a self, includes: I) -> Result<References<'i, S>> {
         let mut references = References {
             explicit_modules: HashSet::new(),
@@ -58,16 +141,77 @@ impl Session {
     }
 
     pub fn parse_module<P: AsRef<Path>>(&self, p: P) -> Result<Arc<syntax::Module>> {
-        let contents = read_to_string(&p)?;
-        let module = Arc::new(parse_module(&contents)?);
-        let mut parsed = match self.parsed.try_write() {
-            Ok(parsed) => parsed,
-            Err(TryLockError::WouldBlock) => return Err(CompilerError::resource_unavailable("session.parsed")),
-            Err(TryLockError::Poisoned(_)) => return Err(CompilerError::ice("a parsing thread panicked".to_string(), CompilerStage::Parsing)),
+        let cached = self.parsed.read().ok()
+            .and_then(|parsed| parsed.get(p.as_ref()).map(|parsed| (parsed.module().clone(), parsed.errors())));
+        let (module, errors) = match cached {
+            Some(cached) => cached,
+            None => self.load(p.as_ref())?,
         };
-        let handle = parsed.entry(p.as_ref().to_owned())
-            .or_insert(module);
-        Ok(handle.clone())
+        if !errors.is_empty() {
+            let source = self.source(p.as_ref())
+                .ok_or_else(|| CompilerError::ice("parsed module has no source".to_string(), CompilerStage::Parsing))?;
+            let errors = errors.iter()
+                .map(|err| ShaderError::syntax(err, &source))
+                .collect();
+            return Err(CompilerError::shaders(errors));
+        }
+        Ok(module)
+    }
+
+    /// Read and parse a source file, keeping its syntax tree for later edits.
+    fn load(&self, p: &Path) -> Result<(Arc<syntax::Module>, Vec<SyntaxError>)> {
+        let contents = match self.memory.get(p) {
+            Some(text) => text.clone(),
+            None => self.fs.read_to_string(p)?,
+        };
+        let source = Arc::new(SourceFile::new(p.display().to_string(), contents));
+        let parsed = ParsedModule::new(cst::parse(source.text()));
+        write_lock(&self.sources, "session.sources")?
+            .insert(p.to_owned(), source);
+        let mut modules = write_lock(&self.parsed, "session.parsed")?;
+        // another thread may have parsed the file first
+        let parsed = modules.entry(p.to_owned())
+            .or_insert(parsed);
+        Ok((parsed.module().clone(), parsed.errors()))
+    }
+
+    /// Apply a text edit to a source file, reading it first if needed. Only
+    /// the tokens around the edit are lexed again, and only the items it
+    /// touches are parsed again; the IDs of the other items are kept.
+    pub fn apply_edit<P: AsRef<Path>>(&self, p: P, edit: &TextEdit) -> Result<ItemChanges> {
+        if self.source(p.as_ref()).is_none() {
+            self.load(p.as_ref())?;
+        }
+        let mut modules = write_lock(&self.parsed, "session.parsed")?;
+        let parsed = modules.get_mut(p.as_ref())
+            .ok_or_else(|| CompilerError::ice("loaded module was not parsed".to_string(), CompilerStage::Parsing))?;
+        let text = parsed.tree().text();
+        if !edit.applies_to(&text) {
+            return Err(InputError::Edit(format!("{:?} in {}", edit.span().range(), p.as_ref().display())).into());
+        }
+
+        let changes = parsed.edit(edit);
+        let source = Arc::new(SourceFile::new(p.as_ref().display().to_string(), parsed.tree().text()));
+        write_lock(&self.sources, "session.sources")?
+            .insert(p.as_ref().to_owned(), source);
+        // any module may import from the edited one
+        write_lock(&self.imports, "session.imports")?
+            .clear();
+        // the edit may add, remove or retype `declare const` items
+        write_lock(&self.bound, "session.bound")?
+            .clear();
+        Ok(changes)
+    }
+
+    /// The text and line index of a file read by `parse_module`.
+    pub fn source<P: AsRef<Path>>(&self, p: P) -> Option<Arc<SourceFile>> {
+        let sources = self.sources.read().ok()?;
+        sources.get(p.as_ref()).cloned()
+    }
+
+    /// The module path of a discovered source file.
+    pub fn module_path<P: AsRef<Path>>(&self, p: P) -> Option<ast::Path> {
+        self.source_store.module_path(p.as_ref()).cloned()
     }
 
     pub fn lower_functions(&self) {
@@ -79,9 +223,41 @@ impl Session {
     }
 }
 
+/// Parse the module path of an `Input::Source`, such as `lighting::pbr`.
+fn source_module_path(module: &str) -> Result<ast::Path> {
+    let is_identifier = |component: &str| {
+        component.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && component.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    if !module.split("::").all(is_identifier) {
+        return Err(CompilerError::include_error(module));
+    }
+    Ok(ast::Path::from(module.split("::")))
+}
+
+/// Take a write lock without waiting for other threads.
+fn write_lock<'a, T>(lock: &'a RwLock<T>, resource: &'static str) -> Result<RwLockWriteGuard<'a, T>> {
+    match lock.try_write() {
+        Ok(guard) => Ok(guard),
+        Err(TryLockError::WouldBlock) => Err(CompilerError::resource_unavailable(resource)),
+        Err(TryLockError::Poisoned(_)) => Err(CompilerError::ice("a parsing thread panicked".to_string(), CompilerStage::Parsing)),
+    }
+}
+
+/// The root of modules given as `Input::Source`.
+const MEMORY_ROOT: &str = "<memory>";
+
 pub struct SourceStore {
     roots: Vec<PathBuf>,
-    modules: HashMap<ast::Path, PathBuf>,
+    modules: HashMap<ast::Path, ModuleSource>,
+    /// The extension of module files, without the dot.
+    extension: String,
+}
+
+/// Where a module's source was found.
+struct ModuleSource {
+    file: PathBuf,
+    root: usize,
 }
 
 pub struct SourceInfo {
@@ -93,64 +269,137 @@ impl SourceStore {
         SourceStore {
             roots: Vec::new(),
             modules: HashMap::new(),
+            extension: config::DEFAULT_EXTENSION.to_owned(),
         }
     }
 
-    fn discover_tree<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        if self.roots.iter().find(|p| *p == path.as_ref()).is_some() {
+    fn discover_tree<P: AsRef<Path>>(&mut self, fs: &dyn FileSystem, path: P) -> Result<()> {
+        if self.roots.iter().any(|p| p == path.as_ref()) {
             // the path has already been traversed
             return Ok(());
         }
+        let files = self.discover_files(fs, path.as_ref())?;
         let root_id = self.roots.len();
-        self.roots.push(path.as_ref().into());
-        self.discover_tree_inner(path, root_id)?;
+        let mut modules = Vec::with_capacity(files.len());
+        for file in files {
+            // convert PathBuf into a Path, stripping extension and root
+            let relative = file.strip_prefix(path.as_ref())
+                .map_err(|err| io::Error::other(format!("StripPrefixError: {}", err)))?;
+            let module_path = relative.with_extension("");
+            let mut temp_path = Vec::new();
+            for component in module_path.components() {
+                if let Component::Normal(component) = component {
+                    if let Some(component) = component.to_str() {
+                        // a directory such as `v1.2` names a module too
+                        if component.contains('.') {
+                            return Err(InputError::DottedModule(file).into());
+                        }
+                        temp_path.push(component.to_owned());
+                    } else {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "path was not utf8").into());
+                    }
+                }
+            }
+            let module_path = ast::Path::from(temp_path.iter());
+            self.check_unique(&module_path, &file)?;
+            modules.push((module_path, ModuleSource { file, root: root_id }));
+        }
 
+        // nothing is registered unless the whole tree is
+        self.roots.push(path.as_ref().into());
+        self.modules.extend(modules);
         Ok(())
     }
 
-    fn discover_tree_inner<P: AsRef<Path>>(&mut self, path: P, root_id: usize) -> io::Result<()> {
-        for entry in read_dir(path)? {
-            let entry = entry?;
-            let kind = entry.file_type()?;
-            if kind.is_dir() {
-                self.discover_tree(entry.path())?;
-            } else if kind.is_file() {
-                let fs_path = entry.path();
-                let mut module_path = fs_path.clone();
-                // convert PathBuf into a Path, stripping extension and root
-                module_path.set_extension("");
-                let prefix = &self.roots[root_id];
-                let mut temp_path = Vec::new();
-                let module_path = module_path.strip_prefix(prefix)
-                    .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("StripPrefixError: {}", err)))?
-                    .components();
-                for component in module_path {
-                    if let Component::Normal(component) = component {
-                        if let Some(component) = component.to_str() {
-                            temp_path.push(component);
-                        } else {
-                            return Err(io::Error::new(io::ErrorKind::InvalidData, "path was not utf8"));
-                        }
-                    }
-                }
-                let module_path = ast::Path::from(temp_path.iter());
-                self.modules.insert(module_path, fs_path);
+    /// The files under `root` with the module extension, leaving out those
+    /// excluded by the root's ignore file.
+    fn discover_files(&self, fs: &dyn FileSystem, root: &Path) -> Result<Vec<PathBuf>> {
+        let ignore = match fs.read_to_string(&root.join(IGNORE_FILE)) {
+            Ok(text) => IgnoreRules::parse(&text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => IgnoreRules::default(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut files = Vec::new();
+        self.discover_files_inner(fs, &ignore, root, root, &mut files)?;
+        Ok(files)
+    }
+
+    fn discover_files_inner(&self, fs: &dyn FileSystem, ignore: &IgnoreRules, root: &Path, path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries = fs.read_dir(path)?;
+        // report duplicates in the same order on every platform
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in entries {
+            let relative = entry.path.strip_prefix(root)
+                .map_err(|err| io::Error::other(format!("StripPrefixError: {}", err)))?;
+            if ignore.is_ignored(relative, entry.kind == FileKind::Dir) {
+                continue;
+            }
+            match entry.kind {
+                // nested directories are nested modules of the same root
+                FileKind::Dir => self.discover_files_inner(fs, ignore, root, &entry.path, files)?,
+                FileKind::File if entry.path.extension() == Some(self.extension.as_ref()) => files.push(entry.path),
+                FileKind::File => {},
             }
         }
         Ok(())
     }
 
+    /// Add a module which has no file, returning the path which stands in
+    /// for its file. Such modules share a root of their own.
+    fn add_source(&mut self, module_path: ast::Path) -> Result<PathBuf> {
+        let root = Path::new(MEMORY_ROOT);
+        let root_id = match self.roots.iter().position(|p| p == root) {
+            Some(root_id) => root_id,
+            None => {
+                self.roots.push(root.into());
+                self.roots.len() - 1
+            },
+        };
+        let file = PathBuf::from(format!("<{}>", module_path));
+        self.insert(module_path, ModuleSource { file: file.clone(), root: root_id })?;
+        Ok(file)
+    }
+
+    /// Register a module, unless another file already has its path.
+    fn insert(&mut self, module_path: ast::Path, source: ModuleSource) -> Result<()> {
+        self.check_unique(&module_path, &source.file)?;
+        self.modules.insert(module_path, source);
+        Ok(())
+    }
+
+    /// Check no file other than `file` has the module path.
+    fn check_unique(&self, module_path: &ast::Path, file: &Path) -> Result<()> {
+        match self.modules.get(module_path) {
+            Some(existing) if existing.file != file => Err(InputError::DuplicateModule {
+                module: module_path.to_string(),
+                first: existing.file.clone(),
+                second: file.to_owned(),
+            }.into()),
+            _ => Ok(()),
+        }
+    }
+
     fn identify_potential_source<'a>(&'a self, path: &ast::Path) -> Option<&'a Path> {
         let mut slice = path.clone();
-        while slice.len() > 0 {
+        while !slice.is_empty() {
             let matching_module_path = self.modules.get(&slice);
             if let Some(matching_module_path) = matching_module_path {
-                return Some(matching_module_path);
+                return Some(&matching_module_path.file);
             }
             slice.parent();
         }
         None
     }
+
+    fn module(&self, path: &ast::Path) -> Option<&ModuleSource> {
+        self.modules.get(path)
+    }
+
+    fn module_path(&self, file: &Path) -> Option<&ast::Path> {
+        self.modules.iter()
+            .find(|(_, source)| source.file == file)
+            .map(|(path, _)| path)
+    }
 }
 
 pub struct References<'a, S> {
@@ -163,3 +412,133 @@ impl<'a, S> References<'a, S> {
         self.explicit_modules.iter()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::span::ByteSpan;
+
+    #[test]
+    fn applies_edits_to_sources() {
+        let file = PathBuf::from("shaders/main.xs");
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(file.clone(), "fn f() { a = b; }\nfn g() {}\n".into());
+
+        let session = Session::with_file_system(Arc::new(fs));
+        let before = session.parse_module(&file).unwrap();
+        let changes = session.apply_edit(&file, &TextEdit::insert(15, " c = d;".into())).unwrap();
+        assert_eq!(changes.removed(), &before.ids()[..1]);
+
+        let after = session.parse_module(&file).unwrap();
+        assert_eq!(after.ids()[1], before.ids()[1]);
+        assert_eq!(session.source(&file).unwrap().line(0), Some("fn f() { a = b; c = d; }"));
+
+        session.apply_edit(&file, &TextEdit::delete(ByteSpan::new(0, 2))).unwrap();
+        assert!(matches!(session.parse_module(&file), Err(CompilerError::Shader(_))));
+        assert!(session.apply_edit(&file, &TextEdit::delete(ByteSpan::new(0, 100))).is_err());
+    }
+
+    #[test]
+    fn reads_sources_from_memory() {
+        let mut session = Session::new();
+        let source = |module: &str, text: &str| config::Input::Source { module: module.into(), text: text.into() };
+        session.register_input(&source("lighting::pbr", "pub fn brdf() {}\n")).unwrap();
+        session.register_input(&source("main", "use crate::lighting::pbr::brdf;\nfn frag() {}\n")).unwrap();
+
+        let file = PathBuf::from("<main>");
+        assert_eq!(session.module_path(&file), Some(ast::Path::from(["main"].iter())));
+        assert_eq!(session.parse_module(&file).unwrap().items().len(), 2);
+        let imports = session.resolve_imports(&ast::Path::from(["main"].iter())).unwrap();
+        assert!(imports.get("brdf").unwrap().is_some());
+
+        // a new text for a module replaces the old one
+        session.register_input(&source("main", "fn frag() {}\n")).unwrap();
+        assert_eq!(session.parse_module(&file).unwrap().items().len(), 1);
+        assert!(session.register_input(&source("not a path", "")).is_err());
+    }
+
+    #[test]
+    fn discovers_modules_through_a_file_system() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/main.xs", "use super::common::saturate;\n".into());
+        fs.insert("shaders/common.xs", "pub fn saturate() {}\n".into());
+        let mut session = Session::with_file_system(Arc::new(fs));
+        session.register_input(&config::Input::Path("shaders".into())).unwrap();
+
+        let main = ast::Path::from(["main"].iter());
+        assert_eq!(session.module_path("shaders/main.xs"), Some(main.clone()));
+        assert!(session.resolve_imports(&main).unwrap().get("saturate").unwrap().is_some());
+        assert!(session.register_input(&config::Input::Path("missing".into())).is_err());
+    }
+
+    #[test]
+    fn filters_discovered_files() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/main.xs", String::new());
+        fs.insert("shaders/main.txt", String::new());
+        fs.insert("shaders/README.md", String::new());
+        fs.insert("shaders/.main.xs.swp", String::new());
+        fs.insert("shaders/legacy.glsl", String::new());
+        fs.insert("shaders/gen/noise.xs", String::new());
+        fs.insert("shaders/lighting/pbr.xs", String::new());
+        fs.insert("shaders/.xsignore", "gen/  # generated at build time\n".into());
+        let fs: Arc<dyn FileSystem> = Arc::new(fs);
+
+        let mut session = Session::with_file_system(fs.clone());
+        session.register_input(&config::Input::Path("shaders".into())).unwrap();
+        let mut modules: Vec<String> = session.source_store.modules.keys().map(ToString::to_string).collect();
+        modules.sort();
+        assert_eq!(modules, ["lighting::pbr", "main"]);
+        assert_eq!(session.module_path("shaders/main.xs"), Some(ast::Path::from(["main"].iter())));
+        let files = session.discover_files("shaders").unwrap();
+        assert_eq!(files, [PathBuf::from("shaders/lighting/pbr.xs"), PathBuf::from("shaders/main.xs")]);
+
+        let mut session = Session::with_file_system(fs);
+        session.set_extension("glsl");
+        session.register_input(&config::Input::Path("shaders".into())).unwrap();
+        assert_eq!(session.module_path("shaders/legacy.glsl"), Some(ast::Path::from(["legacy"].iter())));
+        assert_eq!(session.source_store.modules.len(), 1);
+    }
+
+    #[test]
+    fn reports_duplicate_modules() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("a/common.xs", String::new());
+        fs.insert("b/common.xs", String::new());
+        fs.insert("b/extra.xs", String::new());
+        let mut session = Session::with_file_system(Arc::new(fs));
+        session.register_input(&config::Input::Path("a".into())).unwrap();
+
+        let message = session.register_input(&config::Input::Path("b".into())).unwrap_err().to_string();
+        assert!(message.contains("module `common` is found at both a/common.xs and b/common.xs"), "{}", message);
+        // the failed root leaves nothing behind
+        assert_eq!(session.source_store.roots, [PathBuf::from("a")]);
+        assert_eq!(session.module_path("b/extra.xs"), None);
+        let source = config::Input::Source { module: "common".into(), text: String::new() };
+        let message = session.register_input(&source).unwrap_err().to_string();
+        assert!(message.contains("a/common.xs and <common>"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_dotted_module_names() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/main.xs", String::new());
+        fs.insert("shaders/a.b.xs", String::new());
+        let mut session = Session::with_file_system(Arc::new(fs.clone()));
+        let message = session.register_input(&config::Input::Path("shaders".into())).unwrap_err().to_string();
+        assert!(message.contains("shaders/a.b.xs cannot be a module"), "{}", message);
+        assert!(session.source_store.roots.is_empty());
+        assert!(session.source_store.modules.is_empty());
+
+        fs.insert("shaders/.xsignore", "a.b.xs\n".into());
+        let mut session = Session::with_file_system(Arc::new(fs.clone()));
+        session.register_input(&config::Input::Path("shaders".into())).unwrap();
+        assert_eq!(session.source_store.modules.len(), 1);
+
+        fs.insert("shaders/v1.2/main.xs", String::new());
+        let mut session = Session::with_file_system(Arc::new(fs));
+        let message = session.register_input(&config::Input::Path("shaders".into())).unwrap_err().to_string();
+        assert!(message.contains("shaders/v1.2/main.xs cannot be a module"), "{}", message);
+        assert!(session.source_store.modules.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, convert::TryFrom, path::{Path, PathBuf}, sync::Arc};
+
+use crate::{
+    config::EnvVar,
+    error::{CompilerError, CompilerStage, Result, ShaderError},
+    span::{Annotations, SourceFile, SpanMessage},
+    syntax::{self, Builtin, Declare, DeclareConst, ItemKind, Scalar},
+};
+
+use super::{write_lock, Session};
+
+/// The value of a `declare const` item, converted to its declared type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Bool(bool),
+    I32(i32),
+    U32(u32),
+    F32(f32),
+    F64(f64),
+}
+
+impl Session {
+    /// Give a value to every `declare const` item called `name`, replacing
+    /// any value given before.
+    pub fn declare(&mut self, name: String, value: EnvVar<String>) {
+        self.env.insert(name, value);
+    }
+
+    /// Give a value to the `declare const` items called `name` which are
+    /// not given one with `declare`, such as a default set in the config.
+    /// Unlike a declared value, a default need not be taken by any item.
+    pub fn declare_default(&mut self, name: String, value: EnvVar<String>) {
+        self.defaults.insert(name, value);
+    }
+
+    /// Bind the declared values to the `declare const` items of every
+    /// parsed module. Each item must be given a value of its declared type,
+    /// and each declared value must be taken by an item.
+    pub fn bind_env(&self) -> Result<()> {
+        let mut modules: Vec<(PathBuf, Arc<syntax::Module>)> = self.parsed.read()
+            .map_err(|_| CompilerError::ice("a parsing thread panicked".to_string(), CompilerStage::Parsing))?
+            .iter()
+            .map(|(path, parsed)| (path.clone(), parsed.module().clone()))
+            .collect();
+        modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut bound = HashMap::new();
+        let mut used = Vec::new();
+        let mut errors = Vec::new();
+        for (path, module) in &modules {
+            let source = match self.source(path) {
+                Some(source) => source,
+                None => continue,
+            };
+            for item in module.items() {
+                let declare = match item.kind() {
+                    ItemKind::Declare(Declare::Const(declare)) => declare,
+                    _ => continue,
+                };
+                let name = declare.name().str();
+                let value = match self.env.get(name).or_else(|| self.defaults.get(name)) {
+                    Some(value) => value,
+                    None => {
+                        errors.push(missing_error(&source, declare));
+                        continue;
+                    },
+                };
+                used.push(name);
+                match convert(value, declare.type_name().builtin()) {
+                    Ok(value) => {
+                        bound.insert((path.clone(), name.to_owned()), value);
+                    },
+                    Err(message) => {
+                        let type_name = &source.text()[declare.type_name().span().range()];
+                        let message = format!("`{}` is declared as `{}`, but {}", name, type_name, message);
+                        errors.push(Annotations::error(&source, declare.type_name().span(), message));
+                    },
+                }
+            }
+        }
+        for name in self.env.keys() {
+            if !used.contains(&name.as_str()) {
+                let message = format!("a value was declared for `{}`, but no module has a `declare const {}`", name, name);
+                errors.push(Annotations::from_error_message(message));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(CompilerError::shaders(errors.into_iter().map(ShaderError::new).collect()));
+        }
+        *write_lock(&self.bound, "session.bound")? = bound;
+        Ok(())
+    }
+
+    /// The value bound to a `declare const` item by `bind_env`.
+    pub fn env_value<P: AsRef<Path>>(&self, file: P, name: &str) -> Option<ConstValue> {
+        let bound = self.bound.read().ok()?;
+        bound.get(&(file.as_ref().to_owned(), name.to_owned())).copied()
+    }
+}
+
+fn missing_error(source: &Arc<SourceFile>, declare: &DeclareConst) -> Annotations {
+    let name = declare.name().str();
+    Annotations::error(source, declare.span(), format!("no value was declared for `{}`", name))
+        .with_message(SpanMessage::Help(format!("pass `{}` to `Compiler::declare`, or set it under `[env]` in the config", name)))
+}
+
+/// Convert a value to a scalar type. Integers and floats may be given for
+/// floating point types, and strings are parsed as a literal of the type.
+fn convert(value: &EnvVar<String>, builtin: Option<Builtin>) -> std::result::Result<ConstValue, String> {
+    let scalar = match builtin {
+        Some(Builtin::Scalar(scalar)) => scalar,
+        _ => return Err("only `bool`, `i32`, `u32`, `f32` and `f64` constants can be declared".to_string()),
+    };
+    let converted = match (value, scalar) {
+        (EnvVar::Bool(value), Scalar::Bool) => Some(ConstValue::Bool(*value)),
+        (EnvVar::Integer(value), Scalar::I32) => Some(ConstValue::I32(*value)),
+        (EnvVar::Integer(value), Scalar::U32) => u32::try_from(*value).ok().map(ConstValue::U32),
+        (EnvVar::Integer(value), Scalar::F32) => Some(ConstValue::F32(*value as f32)),
+        (EnvVar::Integer(value), Scalar::F64) => Some(ConstValue::F64(*value as f64)),
+        (EnvVar::Float(value), Scalar::F32) => Some(ConstValue::F32(*value as f32)),
+        (EnvVar::Float(value), Scalar::F64) => Some(ConstValue::F64(*value)),
+        (EnvVar::String(text), Scalar::Bool) => text.parse().ok().map(ConstValue::Bool),
+        (EnvVar::String(text), Scalar::I32) => text.parse().ok().map(ConstValue::I32),
+        (EnvVar::String(text), Scalar::U32) => text.parse().ok().map(ConstValue::U32),
+        (EnvVar::String(text), Scalar::F32) => text.parse().ok().map(ConstValue::F32),
+        (EnvVar::String(text), Scalar::F64) => text.parse().ok().map(ConstValue::F64),
+        _ => None,
+    };
+    converted.ok_or_else(|| match value {
+        EnvVar::Bool(value) => format!("was given the bool `{}`", value),
+        EnvVar::Integer(value) => format!("was given the integer `{}`", value),
+        EnvVar::Float(value) => format!("was given the float `{}`", value),
+        EnvVar::String(text) => format!("was given the string {:?}", text),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cst::TextEdit, session::MemoryFileSystem, span::ByteSpan};
+
+    fn session(text: &str) -> (Session, PathBuf) {
+        let file = PathBuf::from("shaders/main.xs");
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(file.clone(), text.into());
+        let session = Session::with_file_system(Arc::new(fs));
+        session.parse_module(&file).unwrap();
+        (session, file)
+    }
+
+    #[test]
+    fn binds_declared_values() {
+        let (mut session, file) = session("declare const SHADOWS: bool;\ndeclare const MAX_LIGHTS: u32;\ndeclare const GAMMA: f32;\ndeclare const EXPOSURE: f64;\n");
+        session.declare("SHADOWS".into(), EnvVar::Bool(true));
+        session.declare("MAX_LIGHTS".into(), EnvVar::Integer(8));
+        session.declare("GAMMA".into(), EnvVar::String("2.2".into()));
+        session.declare("EXPOSURE".into(), EnvVar::Float(1.5));
+        session.bind_env().unwrap();
+        assert_eq!(session.env_value(&file, "EXPOSURE"), Some(ConstValue::F64(1.5)));
+        assert_eq!(session.env_value(&file, "SHADOWS"), Some(ConstValue::Bool(true)));
+        assert_eq!(session.env_value(&file, "MAX_LIGHTS"), Some(ConstValue::U32(8)));
+        assert_eq!(session.env_value(&file, "GAMMA"), Some(ConstValue::F32(2.2)));
+    }
+
+    #[test]
+    fn reports_mismatched_values() {
+        let (mut session, _) = session("declare const SHADOWS: bool;\ndeclare const MAX_LIGHTS: u32;\ndeclare const TINT: vec3;\ndeclare const STEPS: i32;\n");
+        session.declare("SHADOWS".into(), EnvVar::Integer(1));
+        session.declare("STEPS".into(), EnvVar::Float(2.5));
+        session.declare("MAX_LIGHTS".into(), EnvVar::Integer(-1));
+        session.declare("TINT".into(), EnvVar::String("1.0".into()));
+        let message = session.bind_env().unwrap_err().to_string();
+        assert!(message.contains("`SHADOWS` is declared as `bool`, but was given the integer `1`"), "{}", message);
+        assert!(message.contains("`MAX_LIGHTS` is declared as `u32`, but was given the integer `-1`"), "{}", message);
+        assert!(message.contains("`TINT` is declared as `vec3`, but only"), "{}", message);
+        assert!(message.contains("`STEPS` is declared as `i32`, but was given the float `2.5`"), "{}", message);
+    }
+
+    #[test]
+    fn reports_missing_and_extra_values() {
+        let (mut session, _) = session("declare const SHADOWS: bool;\n");
+        session.declare("SHADOW".into(), EnvVar::Bool(true));
+        let message = session.bind_env().unwrap_err().to_string();
+        assert!(message.contains("no value was declared for `SHADOWS`"), "{}", message);
+        assert!(message.contains("a value was declared for `SHADOW`"), "{}", message);
+    }
+
+    #[test]
+    fn binds_defaults_unless_declared() {
+        let (mut session, file) = session("declare const SHADOWS: bool;\n");
+        session.declare_default("SHADOWS".into(), EnvVar::Bool(false));
+        session.declare_default("UNUSED".into(), EnvVar::Integer(1));
+        session.bind_env().unwrap();
+        assert_eq!(session.env_value(&file, "SHADOWS"), Some(ConstValue::Bool(false)));
+
+        session.declare("SHADOWS".into(), EnvVar::Bool(true));
+        session.bind_env().unwrap();
+        assert_eq!(session.env_value(&file, "SHADOWS"), Some(ConstValue::Bool(true)));
+    }
+
+    #[test]
+    fn forgets_bound_values_after_edits() {
+        let (mut session, file) = session("declare const SHADOWS: bool;\n");
+        session.declare("SHADOWS".into(), EnvVar::Bool(true));
+        session.bind_env().unwrap();
+        assert_eq!(session.env_value(&file, "SHADOWS"), Some(ConstValue::Bool(true)));
+
+        session.apply_edit(&file, &TextEdit::new(ByteSpan::new(23, 27), "i32".into())).unwrap();
+        assert_eq!(session.env_value(&file, "SHADOWS"), None);
+        assert!(session.bind_env().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::{Component, Path};
+
+/// The file in a root listing sources which are not modules.
+pub const IGNORE_FILE: &str = ".xsignore";
+
+/// Patterns read from an ignore file, one per line:
+///
+/// ```text
+/// # comments and blank lines are skipped
+/// *.generated.xs      # a file name, at any depth
+/// vendor/             # a directory, with everything in it
+/// tests/**/fixture.xs # a path from the root
+/// ```
+///
+/// `*` matches within a path component, `?` matches one character and `**`
+/// matches any number of components.
+#[derive(Debug, Clone, Default)]
+pub(super) struct IgnoreRules {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    components: Vec<String>,
+    /// Whether the pattern only matches from the root, rather than any
+    /// file or directory with a matching name.
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRules {
+    pub(super) fn parse(text: &str) -> IgnoreRules {
+        let patterns = text.lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let dir_only = line.ends_with('/');
+                let line = line.trim_end_matches('/');
+                let anchored = line.contains('/');
+                let components = line.trim_start_matches('/')
+                    .split('/')
+                    .map(str::to_owned)
+                    .collect();
+                Pattern { components, anchored, dir_only }
+            })
+            .collect();
+        IgnoreRules { patterns }
+    }
+
+    /// Whether a file or directory, given relative to its root, is ignored.
+    pub(super) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let components: Vec<&str> = path.components()
+            .filter_map(|component| match component {
+                Component::Normal(component) => component.to_str(),
+                _ => None,
+            })
+            .collect();
+        self.patterns.iter()
+            .filter(|pattern| is_dir || !pattern.dir_only)
+            .any(|pattern| if pattern.anchored {
+                matches_path(&pattern.components, &components)
+            } else {
+                components.last().is_some_and(|name| matches_component(&pattern.components[0], name))
+            })
+    }
+}
+
+fn matches_path(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| matches_path(rest, &path[skip..]))
+        },
+        Some((first, rest)) => match path.split_first() {
+            Some((component, path)) => matches_component(first, component) && matches_path(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn matches_component(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some((c, rest)) => match name.split_first() {
+                Some((n, name)) => (*c == '?' || c == n) && matches(rest, name),
+                None => false,
+            },
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_ignore_patterns() {
+        let rules = IgnoreRules::parse("
+            # generated code
+            *.gen.xs
+            vendor/
+            tests/**/fixture?.xs
+            /scratch.xs
+        ");
+        let ignored = |path: &str, is_dir: bool| rules.is_ignored(Path::new(path), is_dir);
+        assert!(ignored("a.gen.xs", false));
+        assert!(ignored("lighting/pbr.gen.xs", false));
+        assert!(!ignored("lighting/pbr.xs", false));
+        assert!(ignored("lighting/vendor", true));
+        assert!(!ignored("vendor", false));
+        assert!(ignored("tests/fixture1.xs", false));
+        assert!(ignored("tests/a/b/fixture2.xs", false));
+        assert!(!ignored("tests/fixture10.xs", false));
+        assert!(ignored("scratch.xs", false));
+        assert!(!ignored("lighting/scratch.xs", false));
+    }
+}
@@ -0,0 +1,528 @@
+use std::{collections::{hash_map::Entry, HashMap, HashSet}, fmt, sync::Arc};
+
+use crate::{
+    ast,
+    error::{CompilerError, Result, ShaderError},
+    span::{Annotations, ByteSpan, SourceFile, SpanMessage},
+    syntax::{self, Import, ItemKind, PathRoot, Visibility},
+};
+
+use super::Session;
+
+/// The names a module brings into scope with `use`.
+#[derive(Debug, Clone, Default)]
+pub struct Imports {
+    names: HashMap<String, Resolved>,
+    /// Names which globs bind to more than one item.
+    ambiguous: HashMap<String, Vec<Resolved>>,
+}
+
+impl Imports {
+    /// What `name` refers to, which is an error if globs import it from
+    /// more than one place.
+    pub fn get<'a>(&'a self, name: &'a str) -> std::result::Result<Option<&'a Resolved>, Ambiguity<'a>> {
+        match self.ambiguous.get(name) {
+            Some(candidates) => Err(Ambiguity { name, candidates }),
+            None => Ok(self.names.get(name)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&str, &Resolved)> {
+        self.names.iter().map(|(name, resolved)| (name.as_str(), resolved))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// What an imported name refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    /// A whole module, such as `pbr` in `use lighting::pbr;`.
+    Module(ast::Path),
+    /// An item defined in a module, after following any `pub use`.
+    Item {
+        module: ast::Path,
+        name: String,
+    },
+}
+
+/// A name which glob imports bind to different items. The imports are
+/// allowed, but the name cannot be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity<'a> {
+    name: &'a str,
+    candidates: &'a [Resolved],
+}
+
+impl<'a> Ambiguity<'a> {
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Each item the name could refer to, in the order they were imported.
+    pub fn candidates(&self) -> &'a [Resolved] {
+        self.candidates
+    }
+}
+
+impl fmt::Display for Ambiguity<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` is ambiguous, as more than one glob imports it", self.name)
+    }
+}
+
+/// A name another module may be able to import.
+struct Export {
+    resolved: Resolved,
+    visibility: Visibility,
+    /// Where the name is defined, in the exporting module.
+    span: ByteSpan,
+}
+
+/// Why an import could not be resolved.
+enum Failure {
+    /// The path does not lead anywhere; reported at the `use` site.
+    Unresolved(String),
+    /// The path leads to an item which is not `pub`.
+    Private {
+        name: String,
+        module: ast::Path,
+        definition: ByteSpan,
+    },
+    /// A module on the way could not be read or parsed.
+    Compiler(CompilerError),
+}
+
+impl From<CompilerError> for Failure {
+    fn from(error: CompilerError) -> Self {
+        Failure::Compiler(error)
+    }
+}
+
+type Resolution<T> = std::result::Result<T, Failure>;
+
+impl Session {
+    /// Resolve the `use` items of a module, reporting every import which
+    /// cannot be found.
+    pub fn resolve_imports(&self, module: &ast::Path) -> Result<Arc<Imports>> {
+        if let Some(imports) = self.imports.read().ok().and_then(|cache| cache.get(module).cloned()) {
+            return Ok(imports);
+        }
+
+        let (syntax, source) = self.load_module(module)?;
+        let mut imports = Imports::default();
+        let mut globbed = HashSet::new();
+        let mut errors = Vec::new();
+        for item in syntax.items() {
+            let use_item = match item.kind() {
+                ItemKind::Use(use_item) => use_item,
+                _ => continue,
+            };
+            for import in use_item.imports() {
+                let span = import_span(&import).unwrap_or_else(|| use_item.span());
+                let bindings = match self.resolve_import(module, use_item.root(), &import, &mut Vec::new()) {
+                    Ok(bindings) => bindings,
+                    Err(Failure::Unresolved(message)) => {
+                        errors.push(Annotations::error(&source, span, message));
+                        continue;
+                    },
+                    Err(Failure::Private { name, module, definition }) => {
+                        let (_, defined_in) = self.load_module(&module)?;
+                        errors.push(private_error(&source, span, &name, &module, &defined_in, definition));
+                        continue;
+                    },
+                    Err(Failure::Compiler(error)) => return Err(error),
+                };
+
+                let glob = matches!(import, Import::Glob { .. });
+                for (name, resolved) in bindings {
+                    if glob {
+                        // explicit imports shadow glob imports
+                        if imports.names.contains_key(&name) && !globbed.contains(&name) {
+                            continue;
+                        }
+                        // globs binding a name to different items make it
+                        // ambiguous, which is reported where it is used
+                        if let Some(candidates) = imports.ambiguous.get_mut(&name) {
+                            if !candidates.contains(&resolved) {
+                                candidates.push(resolved);
+                            }
+                            continue;
+                        }
+                        globbed.insert(name.clone());
+                        match imports.names.entry(name) {
+                            Entry::Occupied(entry) if *entry.get() != resolved => {
+                                let (name, existing) = entry.remove_entry();
+                                imports.ambiguous.insert(name, vec![existing, resolved]);
+                            },
+                            Entry::Occupied(_) => {},
+                            Entry::Vacant(entry) => {
+                                entry.insert(resolved);
+                            },
+                        }
+                    } else {
+                        match imports.names.get(&name) {
+                            Some(existing) if *existing != resolved && !globbed.contains(&name) => {
+                                errors.push(Annotations::error(&source, span, format!("`{}` is imported more than once", name)));
+                                continue;
+                            },
+                            _ => {},
+                        }
+                        globbed.remove(&name);
+                        imports.ambiguous.remove(&name);
+                        imports.names.insert(name, resolved);
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(CompilerError::shaders(errors.into_iter().map(ShaderError::new).collect()));
+        }
+
+        let imports = Arc::new(imports);
+        if let Ok(mut cache) = self.imports.write() {
+            cache.insert(module.clone(), imports.clone());
+        }
+        Ok(imports)
+    }
+
+    /// The names bound by one import of a `use` in module `from`.
+    fn resolve_import(
+        &self,
+        from: &ast::Path,
+        root: PathRoot,
+        import: &Import,
+        visiting: &mut Vec<ast::Path>,
+    ) -> Resolution<Vec<(String, Resolved)>> {
+        let (base, source_root) = self.path_base(from, root)?;
+        match import {
+            Import::Single { path, name } => {
+                let full: Vec<&str> = base.iter().copied().chain(path.iter().map(|i| i.str())).collect();
+                let resolved = self.lookup(from, &full, source_root, visiting)?;
+                Ok(vec![(name.str().to_owned(), resolved)])
+            },
+            Import::Glob { path } => {
+                if base.is_empty() && path.is_empty() {
+                    return Err(Failure::Unresolved("expected a module path before `*`".to_string()));
+                }
+                let full = ast::Path::from(base.iter().copied().chain(path.iter().map(|i| i.str())));
+                if !self.is_module(&full, source_root) {
+                    return Err(Failure::Unresolved(format!("no module `{}`", full)));
+                }
+                let exports = self.exports(&full, visiting)?;
+                Ok(exports.into_iter()
+                    .filter(|(_, export)| visible(export, &full, from))
+                    .map(|(name, export)| (name, export.resolved))
+                    .collect())
+            },
+        }
+    }
+
+    /// The module path a `use` path starts from, and the source root it is
+    /// confined to, if any.
+    fn path_base<'p>(&self, from: &'p ast::Path, root: PathRoot) -> Resolution<(Vec<&'p str>, Option<usize>)> {
+        let from_root = self.source_store.module(from).map(|source| source.root);
+        match root {
+            PathRoot::Source => Ok((Vec::new(), None)),
+            PathRoot::Crate => Ok((Vec::new(), from_root)),
+            PathRoot::Super(depth) => {
+                let components: Vec<&str> = from.components().collect();
+                if depth > components.len() {
+                    return Err(Failure::Unresolved(format!("too many `super` in path from `{}`", from)));
+                }
+                Ok((components[..components.len() - depth].to_vec(), from_root))
+            },
+        }
+    }
+
+    /// The module or item at `path`, as seen from module `from`.
+    fn lookup(&self, from: &ast::Path, path: &[&str], source_root: Option<usize>, visiting: &mut Vec<ast::Path>) -> Resolution<Resolved> {
+        let as_module = ast::Path::from(path.iter());
+        if self.is_module(&as_module, source_root) {
+            return Ok(Resolved::Module(as_module));
+        }
+
+        let unresolved = || Failure::Unresolved(format!("unresolved import `{}`", as_module));
+        let (name, parent) = path.split_last().ok_or_else(unresolved)?;
+        let module = ast::Path::from(parent.iter());
+        if parent.is_empty() || !self.is_module(&module, source_root) {
+            return Err(unresolved());
+        }
+
+        let mut exports = self.exports(&module, visiting)?;
+        let export = exports.remove(*name)
+            .ok_or_else(|| Failure::Unresolved(format!("`{}` not found in `{}`", name, module)))?;
+        if !visible(&export, &module, from) {
+            return Err(Failure::Private {
+                name: name.to_string(),
+                module,
+                definition: export.span,
+            });
+        }
+        Ok(export.resolved)
+    }
+
+    /// Every name another module might import from `module`: the items it
+    /// defines, and the names it re-exports with `pub use`. Private items
+    /// are included so that importing one can be reported as such.
+    fn exports(&self, module: &ast::Path, visiting: &mut Vec<ast::Path>) -> Resolution<HashMap<String, Export>> {
+        if visiting.contains(module) {
+            return Err(Failure::Unresolved(format!("`{}` re-exports itself", module)));
+        }
+
+        let (syntax, _) = self.load_module(module)?;
+        let mut exports = HashMap::new();
+        for item in syntax.items() {
+            match item.kind() {
+                ItemKind::Use(use_item) => {
+                    if item.visibility() != Visibility::Public {
+                        continue;
+                    }
+                    visiting.push(module.clone());
+                    for import in use_item.imports() {
+                        // a broken re-export is reported when its own module
+                        // is resolved
+                        let span = import_span(&import).unwrap_or_else(|| use_item.span());
+                        match self.resolve_import(module, use_item.root(), &import, visiting) {
+                            Ok(bindings) => exports.extend(bindings.into_iter().map(|(name, resolved)| {
+                                (name, Export { resolved, visibility: Visibility::Public, span })
+                            })),
+                            Err(Failure::Unresolved(_) | Failure::Private { .. }) => {},
+                            Err(error) => {
+                                visiting.pop();
+                                return Err(error);
+                            },
+                        }
+                    }
+                    visiting.pop();
+                },
+                kind => {
+                    if let Some(name) = kind.name() {
+                        let resolved = Resolved::Item {
+                            module: module.clone(),
+                            name: name.str().to_owned(),
+                        };
+                        exports.insert(name.str().to_owned(), Export {
+                            resolved,
+                            visibility: item.visibility(),
+                            span: name.span(),
+                        });
+                    }
+                },
+            }
+        }
+        Ok(exports)
+    }
+
+    fn is_module(&self, path: &ast::Path, source_root: Option<usize>) -> bool {
+        match self.source_store.module(path) {
+            Some(source) => source_root.is_none_or(|root| root == source.root),
+            None => false,
+        }
+    }
+
+    fn load_module(&self, path: &ast::Path) -> Result<(Arc<syntax::Module>, Arc<crate::span::SourceFile>)> {
+        let file = match self.source_store.module(path) {
+            Some(source) => source.file.clone(),
+            None => return Err(CompilerError::include_error(&path.to_string())),
+        };
+        let module = self.parse_module(&file)?;
+        let source = self.source(&file)
+            .ok_or_else(|| CompilerError::include_error(&path.to_string()))?;
+        Ok((module, source))
+    }
+}
+
+/// Whether `export`, from `module`, may be imported by module `from`.
+/// Private items are only visible within the module defining them.
+fn visible(export: &Export, module: &ast::Path, from: &ast::Path) -> bool {
+    export.visibility == Visibility::Public || module == from
+}
+
+fn private_error(
+    source: &Arc<SourceFile>,
+    span: ByteSpan,
+    name: &str,
+    module: &ast::Path,
+    defined_in: &Arc<SourceFile>,
+    definition: ByteSpan,
+) -> Annotations {
+    Annotations::error(source, span, format!("`{}` is private to `{}`", name, module))
+        .with_label(defined_in, definition, SpanMessage::Note(format!("`{}` is defined here", name)))
+        .with_message(SpanMessage::Help(format!("mark `{}` as `pub` to use it from other modules", name)))
+}
+
+/// The span of the path named by an import.
+fn import_span(import: &Import) -> Option<ByteSpan> {
+    let path = match import {
+        Import::Single { path, name } => return Some(path[0].span().to(name.span())),
+        Import::Glob { path } => path,
+    };
+    let first = path.first()?;
+    let last = path.last()?;
+    Some(first.span().to(last.span()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{config, session::MemoryFileSystem, span::Renderer};
+
+    /// Open a session over `files`, kept in memory under one root.
+    fn session(files: &[(&str, &str)]) -> Session {
+        let mut fs = MemoryFileSystem::new();
+        for (path, text) in files {
+            fs.insert(format!("shaders/{}", path), text.to_string());
+        }
+
+        let mut session = Session::with_file_system(Arc::new(fs));
+        session.register_input(&config::Input::Path("shaders".into())).unwrap();
+        session
+    }
+
+    fn path(source: &str) -> ast::Path {
+        ast::Path::from(source.split("::"))
+    }
+
+    fn item(module: &str, name: &str) -> Resolved {
+        Resolved::Item {
+            module: path(module),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn resolves_use_trees() {
+        let session = session(&[
+            ("common.xs", "pub fn saturate() {}\npub struct Material { albedo: vec3 }\n"),
+            ("lighting/pbr.xs", "pub use crate::common::saturate;\npub fn brdf() {}\nfn ggx() {}\n"),
+            ("lighting/shadow.xs", "use super::pbr::brdf as specular;\npub fn pcf() {}\n"),
+            ("main.xs", "
+                use lighting::{pbr, shadow::pcf as filter};
+                use lighting::pbr::*;
+                use common::Material;
+                fn frag() {}
+            "),
+        ]);
+
+        let imports = session.resolve_imports(&path("main")).unwrap();
+        assert_eq!(imports.get("pbr"), Ok(Some(&Resolved::Module(path("lighting::pbr")))));
+        assert_eq!(imports.get("filter"), Ok(Some(&item("lighting::shadow", "pcf"))));
+        assert_eq!(imports.get("brdf"), Ok(Some(&item("lighting::pbr", "brdf"))));
+        assert_eq!(imports.get("saturate"), Ok(Some(&item("common", "saturate"))));
+        assert_eq!(imports.get("Material"), Ok(Some(&item("common", "Material"))));
+        assert_eq!(imports.len(), 5);
+
+        let imports = session.resolve_imports(&path("lighting::shadow")).unwrap();
+        assert_eq!(imports.get("specular"), Ok(Some(&item("lighting::pbr", "brdf"))));
+    }
+
+    #[test]
+    fn reports_unresolved_imports() {
+        let session = session(&[
+            ("lighting/pbr.xs", "pub fn brdf() {}\n"),
+            ("main.xs", "
+                use lighting::pbr::missing;
+                use nowhere::x;
+                use super::super::a;
+                use lighting::*;
+                use lighting::pbr::brdf;
+            "),
+        ]);
+
+        let errors = match session.resolve_imports(&path("main")) {
+            Err(CompilerError::Shaders(errors)) => errors,
+            other => panic!("expected errors, got {:?}", other),
+        };
+        let messages: Vec<_> = errors.errors().iter()
+            .map(|error| error.annotations().message().unwrap().text().to_owned())
+            .collect();
+        assert_eq!(messages, [
+            "`missing` not found in `lighting::pbr`",
+            "unresolved import `nowhere::x`",
+            "too many `super` in path from `main`",
+            "no module `lighting`",
+        ]);
+        assert_eq!(errors.errors()[0].annotations().primary.start.line, 1);
+    }
+
+    #[test]
+    fn rejects_private_imports() {
+        let session = session(&[
+            ("lighting.xs", "pub fn shade() {}\nfn ggx() {}\nuse common::Hidden;\n"),
+            ("common.xs", "pub struct Visible { a: A }\nstruct Hidden { a: A }\n"),
+            ("main.xs", "use lighting::*;\nuse lighting::ggx;\nuse lighting::Hidden;\n"),
+        ]);
+
+        let error = match session.resolve_imports(&path("main")) {
+            Err(CompilerError::Shaders(errors)) => errors,
+            other => panic!("expected errors, got {:?}", other),
+        };
+        assert_eq!(error.errors().len(), 2);
+        let rendered = Renderer::plain().render(error.errors()[0].annotations());
+        assert!(rendered.starts_with("error: `ggx` is private to `lighting`\n"), "{}", rendered);
+        assert!(rendered.contains("- `ggx` is defined here"), "{}", rendered);
+        assert!(rendered.contains("= help: mark `ggx` as `pub` to use it from other modules"), "{}", rendered);
+        // private uses are not re-exported
+        let message = error.errors()[1].annotations().message().unwrap().text();
+        assert_eq!(message, "`Hidden` not found in `lighting`");
+
+        // globs skip private items, and modules can use their own
+        let session = self::session(&[
+            ("lighting.xs", "pub fn shade() {}\nfn ggx() {}\n"),
+            ("main.xs", "use lighting::*;\n"),
+        ]);
+        let imports = session.resolve_imports(&path("main")).unwrap();
+        assert_eq!(imports.get("shade"), Ok(Some(&item("lighting", "shade"))));
+        assert_eq!(imports.get("ggx"), Ok(None));
+    }
+
+    #[test]
+    fn stops_at_re_export_cycles() {
+        let session = session(&[
+            ("a.xs", "pub use b::*;\npub fn f() {}\n"),
+            ("b.xs", "pub use a::*;\npub fn g() {}\n"),
+            ("main.xs", "use a::*;\n"),
+        ]);
+
+        let imports = session.resolve_imports(&path("main")).unwrap();
+        assert_eq!(imports.get("f"), Ok(Some(&item("a", "f"))));
+        assert_eq!(imports.get("g"), Ok(Some(&item("b", "g"))));
+    }
+
+    #[test]
+    fn reports_ambiguous_globs_where_used() {
+        let session = session(&[
+            ("a.xs", "pub fn f() {}
+pub fn g() {}
+pub fn h() {}
+"),
+            ("b.xs", "pub fn f() {}
+pub use a::g;
+pub fn h() {}
+"),
+            ("main.xs", "use a::*;
+use b::*;
+use b::h;
+"),
+        ]);
+
+        let imports = session.resolve_imports(&path("main")).unwrap();
+        let ambiguity = imports.get("f").unwrap_err();
+        assert_eq!(ambiguity.to_string(), "`f` is ambiguous, as more than one glob imports it");
+        assert_eq!(ambiguity.candidates(), [item("a", "f"), item("b", "f")]);
+        // the same item through both globs, and a name shadowed by an
+        // explicit import, are not ambiguous
+        assert_eq!(imports.get("g"), Ok(Some(&item("a", "g"))));
+        assert_eq!(imports.get("h"), Ok(Some(&item("b", "h"))));
+    }
+}
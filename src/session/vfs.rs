@@ -0,0 +1,131 @@
+use std::{collections::BTreeMap, fs, io, path::{Component, Path, PathBuf}};
+
+/// Where a session reads its sources from. The session only reads through
+/// this trait, so sources can come from an editor's buffers, an archive or
+/// memory instead of the disk.
+pub trait FileSystem: Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// The files and directories directly inside a directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    pub kind: FileKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+}
+
+/// The real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let kind = entry.file_type()?;
+            let kind = if kind.is_dir() {
+                FileKind::Dir
+            } else if kind.is_file() {
+                FileKind::File
+            } else {
+                continue;
+            };
+            entries.push(DirEntry { path: entry.path(), kind });
+        }
+        Ok(entries)
+    }
+}
+
+/// Files held in memory. Directories exist wherever a file is nested in
+/// them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// Add a file, replacing any file at the same path.
+    pub fn insert<P: Into<PathBuf>>(&mut self, path: P, text: String) {
+        self.files.insert(path.into(), text);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<String> {
+        self.files.remove(path)
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files.get(path)
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries: Vec<DirEntry> = Vec::new();
+        for file in self.files.keys() {
+            let mut rest = match file.strip_prefix(path) {
+                Ok(rest) => rest.components(),
+                Err(_) => continue,
+            };
+            let name = match rest.next() {
+                Some(Component::Normal(name)) => name,
+                _ => continue,
+            };
+            let kind = if rest.next().is_some() { FileKind::Dir } else { FileKind::File };
+            let entry = DirEntry { path: path.join(name), kind };
+            // files are sorted, so the files of a directory are together
+            if entries.last() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+        if entries.is_empty() {
+            return Err(not_found(path));
+        }
+        Ok(entries)
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} is not in memory", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lists_memory_directories() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/main.xs", "fn main() {}".into());
+        fs.insert("shaders/lighting/pbr.xs", String::new());
+        fs.insert("shaders/lighting/shadow.xs", String::new());
+        fs.insert("other/a.xs", String::new());
+
+        let entries = fs.read_dir(Path::new("shaders")).unwrap();
+        assert_eq!(entries, [
+            DirEntry { path: "shaders/lighting".into(), kind: FileKind::Dir },
+            DirEntry { path: "shaders/main.xs".into(), kind: FileKind::File },
+        ]);
+        assert_eq!(fs.read_dir(Path::new("shaders/lighting")).unwrap().len(), 2);
+        assert_eq!(fs.read_to_string(Path::new("shaders/main.xs")).unwrap(), "fn main() {}");
+        assert_eq!(fs.read_dir(Path::new("missing")).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
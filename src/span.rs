@@ -1,11 +1,38 @@
+use std::{fmt, ops::Range, sync::Arc};
+
+mod render;
+pub use render::Renderer;
+
+/// Everything needed to report a diagnostic: the source files involved,
+/// where the problem is, and the messages describing it.
+///
+/// The first annotation is the headline message, reported at `primary`.
+/// Later annotations with a span label other locations in the source, and
+/// those without are printed as notes beneath the source excerpt.
 #[derive(Debug, Clone)]
 pub struct Annotations {
-    pub sources: Vec<String>,
+    pub sources: Vec<Arc<SourceFile>>,
     pub primary: Span,
     pub annotations: Vec<SourceAnnotation>,
 }
 
 impl Annotations {
+    /// A single error message pointing at a span of a source file.
+    pub fn error(source: &Arc<SourceFile>, span: ByteSpan, message: String) -> Annotations {
+        let primary = source.span(span);
+        Annotations {
+            sources: vec![source.clone()],
+            primary: primary.clone(),
+            annotations: vec![
+                SourceAnnotation {
+                    source: Some(0),
+                    span: Some(primary),
+                    message: SpanMessage::Error(message),
+                }
+            ]
+        }
+    }
+
     pub fn from_error_message(s: String) -> Annotations {
         Annotations {
             sources: vec![],
@@ -13,35 +40,81 @@ impl Annotations {
             annotations: vec![
                 SourceAnnotation {
                     source: None,
+                    span: None,
                     message: SpanMessage::Error(s),
-                    annotations: vec![],
                 }
             ]
         }
     }
+
+    /// Label another span of a source file.
+    pub fn with_label(mut self, source: &Arc<SourceFile>, span: ByteSpan, message: SpanMessage) -> Annotations {
+        let index = self.source_index(source);
+        self.annotations.push(SourceAnnotation {
+            source: Some(index),
+            span: Some(source.span(span)),
+            message,
+        });
+        self
+    }
+
+    /// Add a message which is not tied to any location, such as a help or
+    /// note line.
+    pub fn with_message(mut self, message: SpanMessage) -> Annotations {
+        self.annotations.push(SourceAnnotation {
+            source: None,
+            span: None,
+            message,
+        });
+        self
+    }
+
+    pub fn message(&self) -> Option<&SpanMessage> {
+        self.annotations.first().map(|a| &a.message)
+    }
+
+    fn source_index(&mut self, source: &Arc<SourceFile>) -> usize {
+        match self.sources.iter().position(|s| Arc::ptr_eq(s, source) || s.name() == source.name()) {
+            Some(index) => index,
+            None => {
+                self.sources.push(source.clone());
+                self.sources.len() - 1
+            },
+        }
+    }
+}
+
+impl fmt::Display for Annotations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Renderer::plain().render(self))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SourceAnnotation {
+    /// Index into `Annotations::sources`.
     pub source: Option<usize>,
+    pub span: Option<Span>,
     pub message: SpanMessage,
-    pub annotations: Vec<usize>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Span {
     pub start: SourcePos,
     pub end: SourcePos,
 }
 
-#[derive(Debug, Clone)]
+/// Zero based line and column, where columns count characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SourcePos {
     pub line: u32,
     pub col: u32,
 }
 
 impl SourcePos {
-    fn new(line: u32, col: u32) -> SourcePos {
+    pub fn new(line: u32, col: u32) -> SourcePos {
         SourcePos {
             line,
             col,
@@ -49,6 +122,135 @@ impl SourcePos {
     }
 }
 
+/// A range of bytes in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ByteSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ByteSpan {
+    pub fn new(start: usize, end: usize) -> ByteSpan {
+        ByteSpan {
+            start,
+            end,
+        }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: ByteSpan) -> ByteSpan {
+        ByteSpan::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<Range<usize>> for ByteSpan {
+    fn from(range: Range<usize>) -> Self {
+        ByteSpan::new(range.start, range.end)
+    }
+}
+
+/// The text of a source file, indexed by line so byte offsets can be
+/// converted into lines and columns.
+#[derive(Clone)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, text: String) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of a zero based line, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_starts.get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let text = &self.text[start..end];
+        Some(text.trim_end_matches(&['\n', '\r'][..]))
+    }
+
+    /// Convert a byte offset into a line and column. Offsets past the end
+    /// of the file are clamped to the end.
+    pub fn position(&self, offset: usize) -> SourcePos {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let col = self.text[self.line_starts[line]..offset].chars().count();
+        SourcePos::new(line as u32, col as u32)
+    }
+
+    /// Convert a line and column back into a byte offset. Positions past
+    /// the end of a line are clamped to its end.
+    pub fn offset(&self, pos: SourcePos) -> usize {
+        let line = match self.line(pos.line as usize) {
+            Some(line) => line,
+            None => return self.text.len(),
+        };
+        let col = line.char_indices()
+            .nth(pos.col as usize)
+            .map_or(line.len(), |(offset, _)| offset);
+        self.line_starts[pos.line as usize] + col
+    }
+
+    pub fn span(&self, span: ByteSpan) -> Span {
+        Span {
+            start: self.position(span.start),
+            end: self.position(span.end),
+        }
+    }
+}
+
+impl fmt::Debug for SourceFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceFile")
+            .field("name", &self.name)
+            .field("lines", &self.line_count())
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum SpanMessage {
     Error(String),
@@ -56,3 +258,64 @@ pub enum SpanMessage {
     Note(String),
     Warning(String),
 }
+
+impl SpanMessage {
+    pub fn text(&self) -> &str {
+        match self {
+            SpanMessage::Error(s)
+            | SpanMessage::Help(s)
+            | SpanMessage::Note(s)
+            | SpanMessage::Warning(s) => s,
+        }
+    }
+
+    pub fn level(&self) -> &'static str {
+        match self {
+            SpanMessage::Error(_) => "error",
+            SpanMessage::Help(_) => "help",
+            SpanMessage::Note(_) => "note",
+            SpanMessage::Warning(_) => "warning",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn maps_offsets_to_positions() {
+        let file = SourceFile::new("test".into(), "ab\ncd\r\n\nxyz".into());
+        assert_eq!(file.line_count(), 4);
+        assert_eq!(file.position(0), SourcePos::new(0, 0));
+        assert_eq!(file.position(2), SourcePos::new(0, 2));
+        assert_eq!(file.position(3), SourcePos::new(1, 0));
+        assert_eq!(file.position(7), SourcePos::new(2, 0));
+        assert_eq!(file.position(9), SourcePos::new(3, 1));
+        assert_eq!(file.position(100), SourcePos::new(3, 3));
+        assert_eq!(file.line(1), Some("cd"));
+        assert_eq!(file.line(2), Some(""));
+        assert_eq!(file.line(4), None);
+        assert_eq!(file.offset(SourcePos::new(3, 1)), 9);
+        assert_eq!(file.offset(SourcePos::new(1, 10)), 5);
+        assert_eq!(file.offset(SourcePos::new(5, 0)), 11);
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let file = SourceFile::new("test".into(), "größe = 1".into());
+        // "größe" is 5 characters but 7 bytes
+        assert_eq!(file.position(7), SourcePos::new(0, 5));
+        // offsets inside a character snap back to its start
+        assert_eq!(file.position(3), SourcePos::new(0, 2));
+    }
+
+    #[test]
+    fn merges_spans() {
+        let a = ByteSpan::new(4, 6);
+        let b = ByteSpan::new(1, 2);
+        assert_eq!(a.to(b), ByteSpan::new(1, 6));
+        assert_eq!(b.to(a), ByteSpan::new(1, 6));
+        assert_eq!(ByteSpan::from(3..5).len(), 2);
+    }
+}
@@ -0,0 +1,332 @@
+use std::fmt::Write;
+
+use super::{Annotations, SourceFile, Span, SpanMessage};
+
+const TAB_WIDTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Renders `Annotations` in the style of rustc:
+///
+/// ```text
+/// error: expected identifier
+///  --> shaders/main.xs:2:9
+///   |
+/// 2 |     let = a;
+///   |         ^
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+
+/// A span of source to underline, and the text to print next to it.
+struct Label<'a> {
+    span: &'a Span,
+    text: &'a str,
+    primary: bool,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Renderer {
+        Renderer {
+            color,
+        }
+    }
+
+    pub fn plain() -> Renderer {
+        Renderer::new(false)
+    }
+
+    pub fn colored() -> Renderer {
+        Renderer::new(true)
+    }
+
+    pub fn render(&self, annotations: &Annotations) -> String {
+        let mut out = String::new();
+        // writing to a String cannot fail
+        let _ = self.write(&mut out, annotations);
+        out
+    }
+
+    fn write(&self, out: &mut String, annotations: &Annotations) -> std::fmt::Result {
+        let (headline, rest) = match annotations.annotations.split_first() {
+            Some(split) => split,
+            None => {
+                writeln!(out, "{}", self.paint(RED, "error"))?;
+                return Ok(());
+            },
+        };
+
+        writeln!(
+            out,
+            "{}{}",
+            self.paint(level_color(&headline.message), &format!("{}:", headline.message.level())),
+            self.paint(BOLD, &format!(" {}", headline.message.text())),
+        )?;
+
+        // group labels by the source file they point into, primary first
+        let primary_source = headline.source.or_else(|| rest.iter().find_map(|a| a.source));
+        let mut groups: Vec<(usize, Vec<Label>)> = Vec::new();
+        if let Some(source) = primary_source {
+            let span = headline.span.as_ref().unwrap_or(&annotations.primary);
+            groups.push((source, vec![Label { span, text: "", primary: true }]));
+        }
+        for annotation in rest {
+            if let (Some(source), Some(span)) = (annotation.source, &annotation.span) {
+                let label = Label {
+                    span,
+                    text: annotation.message.text(),
+                    primary: false,
+                };
+                match groups.iter_mut().find(|(s, _)| *s == source) {
+                    Some((_, labels)) => labels.push(label),
+                    None => groups.push((source, vec![label])),
+                }
+            }
+        }
+
+        let max_line = groups.iter()
+            .flat_map(|(_, labels)| labels.iter().map(|l| l.span.end.line.max(l.span.start.line)))
+            .max()
+            .unwrap_or(0);
+        let gutter = (max_line as usize + 1).to_string().len();
+
+        for (i, (source, labels)) in groups.iter().enumerate() {
+            let file = match annotations.sources.get(*source) {
+                Some(file) => file,
+                None => continue,
+            };
+            let location = labels[0].span;
+            let arrow = if i == 0 { "-->" } else { ":::" };
+            writeln!(
+                out,
+                "{:width$}{} {}:{}:{}",
+                "",
+                self.paint(BLUE, arrow),
+                file.name(),
+                location.start.line + 1,
+                location.start.col + 1,
+                width = gutter,
+            )?;
+            self.write_excerpt(out, file, labels, gutter)?;
+        }
+
+        let footers: Vec<_> = rest.iter()
+            .filter(|a| a.span.is_none())
+            .collect();
+        if !footers.is_empty() {
+            if groups.is_empty() {
+                writeln!(out, "{:width$} {}", "", self.paint(BLUE, "|"), width = gutter)?;
+            }
+            for footer in footers {
+                writeln!(
+                    out,
+                    "{:width$} {} {}: {}",
+                    "",
+                    self.paint(BLUE, "="),
+                    self.paint(BOLD, footer.message.level()),
+                    footer.message.text(),
+                    width = gutter,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_excerpt(&self, out: &mut String, file: &SourceFile, labels: &[Label], gutter: usize) -> std::fmt::Result {
+        let bar = self.paint(BLUE, "|");
+        writeln!(out, "{:width$} {}", "", bar, width = gutter)?;
+
+        let mut lines: Vec<u32> = labels.iter()
+            .flat_map(|l| [l.span.start.line, l.span.end.line])
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let mut prev = None;
+        for line in lines {
+            match prev {
+                // a single skipped line is cheaper to print than elide
+                Some(prev) if line == prev + 2 => {
+                    self.write_line(out, file, prev + 1, gutter)?;
+                },
+                Some(prev) if line > prev + 2 => writeln!(out, "{}", self.paint(BLUE, "..."))?,
+                _ => {},
+            }
+            prev = Some(line);
+
+            let text = self.write_line(out, file, line, gutter)?;
+
+            for label in labels {
+                let span = label.span;
+                let line_len = text.chars().count() as u32;
+                let (start, end) = if span.start.line == line && span.end.line == line {
+                    (span.start.col, span.end.col)
+                } else if span.start.line == line {
+                    (span.start.col, line_len)
+                } else if span.end.line == line {
+                    (0, span.end.col)
+                } else {
+                    continue;
+                };
+                // only print the label text once, on the last line of the span
+                let text_here = if span.end.line == line { label.text } else { "" };
+
+                let offset = display_width(text, 0, start);
+                let width = display_width(text, start, end).max(1);
+                let (mark, color) = if label.primary { ('^', RED) } else { ('-', BLUE) };
+                let marks: String = std::iter::repeat_n(mark, width).collect();
+                let underline = if text_here.is_empty() {
+                    marks
+                } else {
+                    format!("{} {}", marks, text_here)
+                };
+                writeln!(
+                    out,
+                    "{:width$} {} {:offset$}{}",
+                    "",
+                    bar,
+                    "",
+                    self.paint(color, &underline),
+                    width = gutter,
+                    offset = offset,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a numbered source line, returning its text.
+    fn write_line<'f>(&self, out: &mut String, file: &'f SourceFile, line: u32, gutter: usize) -> Result<&'f str, std::fmt::Error> {
+        let text = file.line(line as usize).unwrap_or("");
+        writeln!(
+            out,
+            "{} {} {}",
+            self.paint(BLUE, &format!("{:>width$}", line + 1, width = gutter)),
+            self.paint(BLUE, "|"),
+            expand_tabs(text),
+        )?;
+        Ok(text)
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn level_color(message: &SpanMessage) -> &'static str {
+    match message {
+        SpanMessage::Error(_) => RED,
+        SpanMessage::Warning(_) => YELLOW,
+        SpanMessage::Help(_) | SpanMessage::Note(_) => CYAN,
+    }
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// Printed width of the characters of `text` from column `start` to `end`.
+fn display_width(text: &str, start: u32, end: u32) -> usize {
+    text.chars()
+        .skip(start as usize)
+        .take(end.saturating_sub(start) as usize)
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::span::ByteSpan;
+
+    fn source(text: &str) -> Arc<SourceFile> {
+        Arc::new(SourceFile::new("shaders/main.xs".into(), text.into()))
+    }
+
+    #[test]
+    fn renders_single_error() {
+        let file = source("fn main() {\n    let = a;\n}\n");
+        let annotations = Annotations::error(&file, ByteSpan::new(20, 21), "expected identifier".into());
+
+        assert_eq!(Renderer::plain().render(&annotations), "\
+error: expected identifier
+ --> shaders/main.xs:2:9
+  |
+2 |     let = a;
+  |         ^
+");
+    }
+
+    #[test]
+    fn renders_labels_and_notes() {
+        let file = source("uniform a: A;\n\nfn main() {\n    a = b;\n}\n");
+        let annotations = Annotations::error(&file, ByteSpan::new(31, 32), "cannot assign to uniform `a`".into())
+            .with_label(&file, ByteSpan::new(8, 9), SpanMessage::Note("`a` declared here".into()))
+            .with_message(SpanMessage::Help("uniforms are read only".into()));
+
+        assert_eq!(Renderer::plain().render(&annotations), "\
+error: cannot assign to uniform `a`
+ --> shaders/main.xs:4:5
+  |
+1 | uniform a: A;
+  |         - `a` declared here
+...
+4 |     a = b;
+  |     ^
+  = help: uniforms are read only
+");
+    }
+
+    #[test]
+    fn renders_multiline_spans() {
+        let file = source("fn main() {\n\tlet a = b;\n}");
+        let annotations = Annotations::error(&file, ByteSpan::new(10, 24), "mismatched types".into());
+
+        assert_eq!(Renderer::plain().render(&annotations), "\
+error: mismatched types
+ --> shaders/main.xs:1:11
+  |
+1 | fn main() {
+  |           ^
+2 |     let a = b;
+3 | }
+  | ^
+");
+    }
+
+    #[test]
+    fn renders_without_source() {
+        let annotations = Annotations::from_error_message("no entry point".into())
+            .with_message(SpanMessage::Note("pipelines need a vertex shader".into()));
+
+        assert_eq!(Renderer::plain().render(&annotations), "\
+error: no entry point
+  |
+  = note: pipelines need a vertex shader
+");
+    }
+
+    #[test]
+    fn renders_color() {
+        let file = source("let");
+        let annotations = Annotations::error(&file, ByteSpan::new(0, 3), "oops".into());
+        let rendered = Renderer::colored().render(&annotations);
+        assert!(rendered.starts_with("\x1b[1;31merror:\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^^^\x1b[0m"));
+    }
+}
@@ -1,28 +1,24 @@
-use crate::{error::CompilerError, token::{Token, TokenStream}};
+use std::sync::Arc;
 
-macro_rules! expect {
-    ($lex:ident, $err:ident) => {
-        if let Some(result) = $lex.next() {
-            result
-        } else {
-            return Err(ParseError::$err($lex));
-        }
-    };
-    ($lex:ident, $err:ident, $msg:expr) => {
-        if let Some(result) = $lex.next() {
-            result
-        } else {
-            return Err(ParseError::$err($lex, $msg));
-        }
-    };
-}
+mod builtin;
+mod shift;
+pub mod visit;
+pub use builtin::{Builtin, Dimension, Scalar};
+use shift::Shift;
+use visit::VisitMut;
+
+use logos::Span;
+
+use crate::{cst::{self, GreenElement, GreenNode, NodeKind, SyntaxElement, SyntaxNode, TextEdit}, error::{CompilerError, ShaderError}, glsl, span::{ByteSpan, SourceFile}, token::{self, Token, TokenStream}};
 
 macro_rules! expect_sequence {
     ($tokens:ident, $($token:pat),+) => {{
         fn gather(mut tokens: TokenStream) -> std::result::Result<TokenStream, ParseError> {
             $(
-                match tokens.next() {
-                    Some($token) => {},
+                match tokens.peek() {
+                    Some($token) => {
+                        tokens.next();
+                    },
                     _ => return Err(ParseError::syntax(tokens, &format!("expected {}", stringify!($token)))),
                 }
             )+
@@ -34,8 +30,16 @@ macro_rules! expect_sequence {
 
 macro_rules! expect_identifier {
     ($lex:ident) => {
-        if let Some(Token::Text) = $lex.next() {
-            Identifier($lex.slice_prev().unwrap().to_owned())
+        if let Some(Token::Text) = $lex.peek() {
+            let name = $lex.slice().unwrap();
+            if let Err(message) = validate_identifier(name) {
+                return Err(ParseError::syntax($lex, &message));
+            }
+            $lex.next();
+            Identifier {
+                name: name.to_owned(),
+                span: $lex.span_prev().unwrap().into(),
+            }
         } else {
             return Err(ParseError::identifier($lex));
         }
@@ -53,10 +57,14 @@ macro_rules! return_if {
 
 pub type Result<'source, T> = std::result::Result<(TokenStream<'source>, T), ParseError<'source>>;
 
+/// A parse failure. The stream is left at the token which could not be
+/// parsed.
 #[derive(Debug, Clone)]
 pub struct ParseError<'source> {
     stream: TokenStream<'source>,
     detail: ParseErrorDetail,
+    /// Where to report the error, when that is not the offending token.
+    span: Option<ByteSpan>,
 }
 
 impl<'source> ParseError<'source> {
@@ -66,12 +74,40 @@ impl<'source> ParseError<'source> {
         ParseError {
             stream,
             detail: ParseErrorDetail::Syntax(message.to_string()),
+            span: None,
         }
     }
 
+    /// Report the error at `span` rather than at the offending token.
+    fn at(mut self, span: ByteSpan) -> ParseError<'source> {
+        self.span = Some(span);
+        self
+    }
+
     fn identifier(stream: TokenStream) -> ParseError {
         ParseError::syntax(stream, "expected identifier")
     }
+
+    pub fn message(&self) -> &str {
+        match &self.detail {
+            ParseErrorDetail::Syntax(message) => message,
+        }
+    }
+
+    /// The span of the offending token, or an empty span at the end of the
+    /// source if the error was at end of file.
+    pub fn span(&self) -> ByteSpan {
+        if let Some(span) = self.span {
+            return span;
+        }
+        match self.stream.span() {
+            Some(span) => span.into(),
+            None => {
+                let end = self.stream.position();
+                ByteSpan::new(end, end)
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -81,294 +117,3514 @@ enum ParseErrorDetail {
 
 impl<'source> std::fmt::Display for ParseError<'source> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(fmt, "ParseError <undefined> at \"")?;
+        write!(fmt, "{}", self.message())?;
         match self.stream.slice() {
-            Some(text) => write!(fmt, "{}\"; detail: {:?}", text, &self.detail),
-            None => write!(fmt, "<eof>")
+            Some(text) => write!(fmt, ", found `{}`", text),
+            None => write!(fmt, " at end of file"),
         }
-
     }
 }
 
 impl<'source> std::error::Error for ParseError<'source> {}
 
-#[derive(Debug, Clone)]
-pub struct Module {
-    items: Vec<Item>,
+/// A `ParseError` which no longer borrows the token stream.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SyntaxError {
+    message: String,
+    found: Option<String>,
+    span: ByteSpan,
 }
 
-#[derive(Debug, Clone)]
-pub enum Item {
-    Use(Use),
-    Declare(Declare),
-    Global(Global),
-    Function(Function),
-    Struct(Struct),
+impl SyntaxError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Use {
-    pub path: Vec<Identifier>,
+impl<'source> From<&ParseError<'source>> for SyntaxError {
+    fn from(error: &ParseError<'source>) -> SyntaxError {
+        SyntaxError {
+            message: error.message().to_owned(),
+            found: error.stream.slice().map(str::to_owned),
+            span: error.span(),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub enum Declare {
-    Function(DeclareFunction),
-    Type(DeclareType),
-    Const(DeclareConst),
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.message)?;
+        match &self.found {
+            Some(text) => write!(fmt, ", found `{}`", text),
+            None => write!(fmt, " at end of file"),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct DeclareFunction(Identifier, Arguments, TypeName);
+impl std::error::Error for SyntaxError {}
 
-#[derive(Debug, Clone)]
-pub struct DeclareType(Identifier);
+/// The span from `start` to the end of the last token consumed by `tokens`.
+fn span_from(start: usize, tokens: &TokenStream) -> ByteSpan {
+    ByteSpan::new(start, tokens.prev_end().max(start))
+}
 
-#[derive(Debug, Clone)]
-pub struct DeclareConst(Identifier, TypeName);
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Module {
+    items: Vec<Item>,
+    /// The ID of each item, in the same order.
+    ids: Vec<ItemId>,
+    span: ByteSpan,
+}
 
-#[derive(Debug,Clone)]
-pub struct Arguments(Vec<(Identifier, TypeName)>);
+impl Module {
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
 
-#[derive(Debug,Clone)]
-pub enum TypeName {
-    Identifier(Identifier),
-    Tuple(Vec<TypeName>),
-    Literal(Literal),
-}
+    pub fn ids(&self) -> &[ItemId] {
+        &self.ids
+    }
 
-#[derive(Debug,Clone)]
-pub struct Struct {
-    fields: Vec<(Identifier, TypeName)>,
-}
+    pub fn item(&self, id: ItemId) -> Option<&Item> {
+        let index = self.ids.iter().position(|other| *other == id)?;
+        self.items.get(index)
+    }
 
-#[derive(Debug, Clone)]
-pub struct Global {
-    qualifier: GlobalQualifier,
-    identifier: Identifier,
-    definition: TypeName,
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
 }
 
+/// Identifies an item within its module. An item keeps its ID while edits to
+/// the source leave it unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ItemId(u32);
+
+/// An item, along with the doc comments and attributes written before it.
 #[derive(Debug, Clone)]
-pub enum GlobalQualifier {
-    In,
-    Out,
-    Uniform,
-    Const,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Item {
+    kind: ItemKind,
+    visibility: Visibility,
+    docs: Vec<Doc>,
+    attributes: Vec<Attribute>,
+    span: ByteSpan,
 }
 
-#[derive(Debug, Clone)]
-pub struct Identifier(String);
+impl Item {
+    pub fn new(kind: ItemKind, visibility: Visibility, docs: Vec<Doc>, attributes: Vec<Attribute>, span: ByteSpan) -> Item {
+        Item {
+            kind,
+            visibility,
+            docs,
+            attributes,
+            span,
+        }
+    }
 
-impl Identifier {
-    pub fn str(&self) -> &str {
-        &self.0
+    pub fn kind(&self) -> &ItemKind {
+        &self.kind
     }
-}
 
-impl AsRef<str> for Identifier {
-    fn as_ref(&self) -> &str {
-        &self.0
+    pub fn kind_mut(&mut self) -> &mut ItemKind {
+        &mut self.kind
+    }
+
+    pub fn into_kind(self) -> ItemKind {
+        self.kind
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    pub fn docs(&self) -> &[Doc] {
+        &self.docs
+    }
+
+    /// The documentation of the item as one string, one line per line of
+    /// doc comment.
+    pub fn doc_text(&self) -> Option<String> {
+        if self.docs.is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = self.docs.iter().map(Doc::text).collect();
+        Some(lines.join("\n"))
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// The first attribute called `name`.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name.str() == name)
+    }
+
+    /// The span of the item from its first attribute. Doc comments are not
+    /// included.
+    pub fn span(&self) -> ByteSpan {
+        self.span
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Function {
-    name: Identifier,
-    return_type: Option<TypeName>,
-    // arguments,
-    body: Block,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ItemKind {
+    Use(Use),
+    Declare(Declare),
+    Global(Global),
+    Function(Function),
+    Struct(Struct),
+    /// Source which failed to parse. The error itself is reported alongside
+    /// the module.
+    Error,
 }
 
-#[derive(Debug, Clone)]
-pub struct Block {
-    statements: Vec<Statement>,
+impl ItemKind {
+    /// The name the item defines. `use` items bring names into scope rather
+    /// than defining one.
+    pub fn name(&self) -> Option<&Identifier> {
+        match self {
+            ItemKind::Declare(Declare::Function(f)) => Some(&f.name),
+            ItemKind::Declare(Declare::Type(t)) => Some(&t.name),
+            ItemKind::Declare(Declare::Const(c)) => Some(&c.name),
+            ItemKind::Global(g) => Some(&g.identifier),
+            ItemKind::Function(f) => Some(&f.name),
+            ItemKind::Struct(s) => Some(&s.name),
+            ItemKind::Use(_) | ItemKind::Error => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub enum Statement {
-    Assignment(Assignment),
-    Block(Block),
-    Expr(Expr),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Visibility {
+    /// Only visible within the module which defines it.
+    Private,
+    /// Visible to any module which uses it, marked `pub`.
+    Public,
 }
 
+/// A `///` or `/** */` doc comment.
 #[derive(Debug, Clone)]
-pub struct Assignment {
-    binding: Identifier,
-    expression: Expr,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Doc {
+    text: String,
+    span: ByteSpan,
 }
 
-#[derive(Debug, Clone)]
-pub enum Expr {
-    Literal(Literal),
+impl Doc {
+    pub fn new(text: String, span: ByteSpan) -> Doc {
+        Doc {
+            text,
+            span,
+        }
+    }
+
+    /// Parse the doc comment with the given source text, or `None` if it is
+    /// a plain comment.
+    fn parse(comment: &str, span: ByteSpan) -> Option<Doc> {
+        let text = if let Some(line) = comment.strip_prefix("///") {
+            if line.starts_with('/') {
+                return None;
+            }
+            line.strip_prefix(' ').unwrap_or(line).trim_end().to_string()
+        } else if let Some(block) = comment.strip_prefix("/**") {
+            if block.starts_with('*') || block.starts_with('/') {
+                return None;
+            }
+            let block = block.strip_suffix("*/").unwrap_or(block);
+            // drop the leading ` * ` of each line in a multi-line comment
+            let lines: Vec<&str> = block.lines()
+                .map(|line| {
+                    let line = line.trim();
+                    let line = line.strip_prefix('*').unwrap_or(line);
+                    line.strip_prefix(' ').unwrap_or(line)
+                })
+                .collect();
+            lines.join("\n").trim().to_string()
+        } else {
+            return None;
+        };
+        Some(Doc { text, span })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
 }
 
+/// An attribute such as `#[inline]` or `#[location(0)]`.
 #[derive(Debug, Clone)]
-pub enum Literal {
-    String(String),
-    Number(String),
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Attribute {
+    name: Identifier,
+    arguments: Option<Vec<Expr>>,
+    span: ByteSpan,
 }
 
-pub fn parse_module(source: &str) -> std::result::Result<Module, CompilerError> {
-    let buffer = TokenStream::buffer(source);
-    let stream = TokenStream::new(&buffer, source);
+impl Attribute {
+    pub fn new(name: Identifier, arguments: Option<Vec<Expr>>, span: ByteSpan) -> Attribute {
+        Attribute {
+            name,
+            arguments,
+            span,
+        }
+    }
 
-    let (_, module) = module(stream)?;
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
 
-    Ok(module)
+    /// The parenthesised arguments, or `None` for a bare attribute.
+    pub fn arguments(&self) -> Option<&[Expr]> {
+        self.arguments.as_deref()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
 }
 
-pub fn module(mut tokens: TokenStream) -> Result<Module> {
-    let mut module = Module {
-        items: Vec::new(),
-    };
+/// A `use` item such as `use lighting::{pbr, shadow as sh};`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Use {
+    root: PathRoot,
+    tree: UseTree,
+    span: ByteSpan,
+}
 
-    loop {
-        if tokens.eof() {
-            break;
+impl Use {
+    pub fn new(root: PathRoot, tree: UseTree, span: ByteSpan) -> Use {
+        Use {
+            root,
+            tree,
+            span,
         }
+    }
 
-        let result = item(tokens)?;
-        tokens = result.0;
-        module.items.push(result.1);
+    pub fn root(&self) -> PathRoot {
+        self.root
+    }
+
+    pub fn tree(&self) -> &UseTree {
+        &self.tree
+    }
+
+    /// Every import in the tree, with group prefixes applied.
+    pub fn imports(&self) -> Vec<Import<'_>> {
+        let mut imports = Vec::new();
+        self.tree.flatten(&mut Vec::new(), &mut imports);
+        imports
     }
 
-    Ok((tokens, module))
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
 }
 
-pub fn item(tokens: TokenStream) -> Result<Item> {
-    let upcoming = tokens.peek();
-    if let Some(intention) = upcoming {
-        match intention {
-            Token::Use => {
-                return use_item(tokens.clone()).map(|(s, u)| (s, Item::Use(u)));
-            },
-            Token::Declare => {
-                return declare_item(tokens.clone());
-            },
-            Token::Uniform | Token::In | Token::Out => {
-                return global(tokens.clone());
-            },
-            Token::Function => {
-                return function(tokens.clone());
-            },
-            _ => {},
+/// Where a `use` path starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PathRoot {
+    /// A plain path, starting at any source root.
+    Source,
+    /// `crate::`, the source root of the using module.
+    Crate,
+    /// `super::`, repeated the given number of times.
+    Super(usize),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UseTree {
+    path: Vec<Identifier>,
+    kind: UseTreeKind,
+    span: ByteSpan,
+}
+
+impl UseTree {
+    pub fn new(path: Vec<Identifier>, kind: UseTreeKind, span: ByteSpan) -> UseTree {
+        UseTree {
+            path,
+            kind,
+            span,
         }
     }
-    Err(ParseError::syntax(tokens, "expected item"))
-}
 
-pub fn use_item(mut tokens: TokenStream) -> Result<Use> {
-    tokens = expect_sequence!(tokens, Token::Use)?;
-    let mut path = Vec::new();
-    loop {
-        let component = expect_identifier!(tokens);
-        path.push(component);
+    /// The path before any alias, `*` or group. For a simple tree this
+    /// includes the imported name.
+    pub fn path(&self) -> &[Identifier] {
+        &self.path
+    }
 
-        match tokens.next() {
-            Some(Token::Semicolon) => {
-                break;
-            },
-            Some(Token::PathSeparator) => {
+    pub fn kind(&self) -> &UseTreeKind {
+        &self.kind
+    }
 
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+
+    fn flatten<'a>(&'a self, prefix: &mut Vec<&'a Identifier>, imports: &mut Vec<Import<'a>>) {
+        let depth = prefix.len();
+        prefix.extend(&self.path);
+        match &self.kind {
+            UseTreeKind::Simple(alias) => {
+                // a simple tree always has at least one component
+                let name = alias.as_ref().or_else(|| self.path.last()).unwrap();
+                imports.push(Import::Single { path: prefix.clone(), name });
+            },
+            UseTreeKind::Glob => imports.push(Import::Glob { path: prefix.clone() }),
+            UseTreeKind::Group(trees) => {
+                for tree in trees {
+                    tree.flatten(prefix, imports);
+                }
             },
-            _ => {
-                return Err(ParseError::syntax(tokens, "expected path"));
-            }
         }
+        prefix.truncate(depth);
     }
-    Ok((tokens, Use { path }))
 }
 
-pub fn declare_item(tokens: TokenStream) -> Result<Item> {
-    let into_item = |(tokens, d)| (tokens, Item::Declare(d));
-    return_if!(declare_type(tokens.clone()).map(into_item));
-    return_if!(declare_const(tokens.clone()).map(into_item));
-    Err(ParseError::syntax(tokens, "expected declaration"))
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum UseTreeKind {
+    /// `a::b`, or `a::b as c`.
+    Simple(Option<Identifier>),
+    /// `a::*`
+    Glob,
+    /// `a::{b, c}`
+    Group(Vec<UseTree>),
 }
 
-pub fn declare_type(mut tokens: TokenStream) -> Result<Declare> {
-    tokens = expect_sequence!(tokens, Token::Declare, Token::Type)?;
-    let name = expect_identifier!(tokens);
-    // let (mut tokens, name) = match type_name(tokens)? {
-    //     (tokens, TypeName::Identifier(ident)) => (tokens, ident),
-    //     _ => unimplemented!("whoops"),
-    // };
-    tokens = expect_sequence!(tokens, Token::Semicolon)?;
-    Ok((tokens, Declare::Type(DeclareType(name))))
+/// One name, or every name in a module, brought into scope by a `use`.
+#[derive(Debug, Clone)]
+pub enum Import<'a> {
+    /// Binds the item at `path` as `name`, which is the alias if one was
+    /// given.
+    Single {
+        path: Vec<&'a Identifier>,
+        name: &'a Identifier,
+    },
+    /// Binds every public name in the module at `path`.
+    Glob {
+        path: Vec<&'a Identifier>,
+    },
 }
 
-pub fn declare_const(mut tokens: TokenStream) -> Result<Declare> {
-    tokens = expect_sequence!(tokens, Token::Declare, Token::Const)?;
-    let name = expect_identifier!(tokens);
-
-    tokens = expect_sequence!(tokens, Token::Colon)?;
-    let (mut tokens, def) = type_name(tokens)?;
-    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Declare {
+    Function(DeclareFunction),
+    Type(DeclareType),
+    Const(DeclareConst),
+}
 
-    Ok((tokens, Declare::Const(DeclareConst(name, def))))
+impl Declare {
+    pub fn span(&self) -> ByteSpan {
+        match self {
+            Declare::Function(f) => f.span,
+            Declare::Type(t) => t.span,
+            Declare::Const(c) => c.span,
+        }
+    }
 }
 
-pub fn type_name(mut tokens: TokenStream) -> Result<TypeName> {
-    // read type
-    let name = expect_identifier!(tokens);
-    Ok((tokens, TypeName::Identifier(name)))
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeclareFunction {
+    name: Identifier,
+    arguments: Arguments,
+    return_type: TypeName,
+    span: ByteSpan,
 }
 
-pub fn global(mut tokens: TokenStream) -> Result<Item> {
-    const MSG: &str = "expected global qualifier (in, out, or uniform)";
-    let token = expect!(tokens, syntax, MSG);
+impl DeclareFunction {
+    pub fn new(name: Identifier, arguments: Arguments, return_type: TypeName, span: ByteSpan) -> DeclareFunction {
+        DeclareFunction {
+            name,
+            arguments,
+            return_type,
+            span,
+        }
+    }
 
-    let qualifier = match token {
-        Token::In => GlobalQualifier::In,
-        Token::Out => GlobalQualifier::Out,
-        Token::Uniform => GlobalQualifier::Uniform,
-        _ => {
-            return Err(ParseError::syntax(tokens, MSG))
-        },
-    };
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
 
-    let identifier = expect_identifier!(tokens);
-    tokens = expect_sequence!(tokens, Token::Colon)?;
+    pub fn arguments(&self) -> &Arguments {
+        &self.arguments
+    }
 
-    let (mut tokens, definition) = type_name(tokens)?;
-    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+    pub fn return_type(&self) -> &TypeName {
+        &self.return_type
+    }
 
-    let global = Global {
-        qualifier,
-        identifier,
-        definition,
-    };
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
 
-    Ok((tokens, Item::Global(global)))
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeclareType {
+    name: Identifier,
+    span: ByteSpan,
 }
 
-pub fn function(mut tokens: TokenStream) -> Result<Item> {
-    tokens = expect_sequence!(tokens, Token::Function)?;
+impl DeclareType {
+    pub fn new(name: Identifier, span: ByteSpan) -> DeclareType {
+        DeclareType {
+            name,
+            span,
+        }
+    }
 
-    let name = expect_identifier!(tokens);
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
 
-    tokens = expect_sequence!(tokens, Token::LeftParen, Token::RightParen)?;
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
 
-    let mut return_type = None;
-    match tokens.peek() {
-        Some(Token::LeftBrace) => {},
-        _ => {
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeclareConst {
+    name: Identifier,
+    type_name: TypeName,
+    span: ByteSpan,
+}
+
+impl DeclareConst {
+    pub fn new(name: Identifier, type_name: TypeName, span: ByteSpan) -> DeclareConst {
+        DeclareConst {
+            name,
+            type_name,
+            span,
+        }
+    }
+
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    pub fn type_name(&self) -> &TypeName {
+        &self.type_name
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// A parenthesized parameter list; the span includes the parentheses.
+#[derive(Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Arguments {
+    arguments: Vec<(Identifier, TypeName)>,
+    span: ByteSpan,
+}
+
+impl Arguments {
+    pub fn new(arguments: Vec<(Identifier, TypeName)>, span: ByteSpan) -> Arguments {
+        Arguments {
+            arguments,
+            span,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&(Identifier, TypeName)> {
+        self.arguments.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.arguments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arguments.is_empty()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TypeName {
+    kind: TypeNameKind,
+    span: ByteSpan,
+}
+
+impl TypeName {
+    pub fn new(kind: TypeNameKind, span: ByteSpan) -> TypeName {
+        TypeName {
+            kind,
+            span,
+        }
+    }
+
+    pub fn kind(&self) -> &TypeNameKind {
+        &self.kind
+    }
+
+    /// The language type this names, if it is not user defined.
+    pub fn builtin(&self) -> Option<Builtin> {
+        Builtin::of(self)
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TypeNameKind {
+    Identifier(Identifier),
+    /// `(A, B)`, or `()` for the unit type.
+    Tuple(Vec<TypeName>),
+    /// A constant argument of a generic type, as in `vec<f32, 3>`.
+    Literal(Literal),
+    /// `[T; N]`, or `[T]` for an array sized at runtime.
+    Array(Box<TypeName>, Option<Box<Expr>>),
+    /// `name<A, B>`
+    Generic(Identifier, Vec<TypeName>),
+}
+
+#[derive(Debug,Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Struct {
+    name: Identifier,
+    fields: Vec<(Identifier, TypeName)>,
+    span: ByteSpan,
+}
+
+impl Struct {
+    pub fn new(name: Identifier, fields: Vec<(Identifier, TypeName)>, span: ByteSpan) -> Struct {
+        Struct {
+            name,
+            fields,
+            span,
+        }
+    }
+
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    pub fn fields(&self) -> &[(Identifier, TypeName)] {
+        &self.fields
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Global {
+    qualifier: GlobalQualifier,
+    identifier: Identifier,
+    definition: TypeName,
+    initializer: Option<Expr>,
+    span: ByteSpan,
+}
+
+impl Global {
+    pub fn new(qualifier: GlobalQualifier, identifier: Identifier, definition: TypeName, initializer: Option<Expr>, span: ByteSpan) -> Global {
+        Global {
+            qualifier,
+            identifier,
+            definition,
+            initializer,
+            span,
+        }
+    }
+
+    pub fn qualifier(&self) -> &GlobalQualifier {
+        &self.qualifier
+    }
+
+    pub fn identifier(&self) -> &Identifier {
+        &self.identifier
+    }
+
+    pub fn definition(&self) -> &TypeName {
+        &self.definition
+    }
+
+    /// The value of a `const` global. Other globals are provided by the
+    /// pipeline, and have none.
+    pub fn initializer(&self) -> Option<&Expr> {
+        self.initializer.as_ref()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum GlobalQualifier {
+    In,
+    Out,
+    Uniform,
+    Const,
+}
+
+/// Check a name lexed as `Token::Text` may be used as an identifier.
+fn validate_identifier(name: &str) -> std::result::Result<(), String> {
+    if token::is_reserved(name) {
+        Err(format!("`{}` is a reserved word", name))
+    } else if name.starts_with("gl_") {
+        Err(format!("`{}`: identifiers beginning with `gl_` are reserved by GLSL", name))
+    } else if name.contains("__") {
+        Err(format!("`{}`: identifiers containing `__` are reserved by GLSL", name))
+    } else if name.starts_with(glsl::MANGLE_PREFIX) {
+        Err(format!("`{}`: identifiers beginning with `{}` are reserved by the compiler", name, glsl::MANGLE_PREFIX))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Identifier {
+    name: String,
+    span: ByteSpan,
+}
+
+impl Identifier {
+    pub fn new(name: String, span: ByteSpan) -> Identifier {
+        Identifier {
+            name,
+            span,
+        }
+    }
+
+    pub fn str(&self) -> &str {
+        &self.name
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+impl AsRef<str> for Identifier {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Function {
+    name: Identifier,
+    return_type: Option<TypeName>,
+    arguments: Arguments,
+    body: Block,
+    span: ByteSpan,
+}
+
+impl Function {
+    pub fn new(name: Identifier, return_type: Option<TypeName>, arguments: Arguments, body: Block, span: ByteSpan) -> Function {
+        Function {
+            name,
+            return_type,
+            arguments,
+            body,
+            span,
+        }
+    }
+
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    pub fn arguments(&self) -> &Arguments {
+        &self.arguments
+    }
+
+    pub fn return_type(&self) -> Option<&TypeName> {
+        self.return_type.as_ref()
+    }
+
+    pub fn body(&self) -> &Block {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Block {
+        &mut self.body
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// A braced block; the span includes the braces.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Block {
+    statements: Vec<Statement>,
+    /// A trailing expression without a semicolon, giving the block its value.
+    tail: Option<Expr>,
+    span: ByteSpan,
+}
+
+impl Block {
+    pub fn new(statements: Vec<Statement>, tail: Option<Expr>, span: ByteSpan) -> Block {
+        Block {
+            statements,
+            tail,
+            span,
+        }
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn statements_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.statements
+    }
+
+    pub fn tail(&self) -> Option<&Expr> {
+        self.tail.as_ref()
+    }
+
+    pub fn tail_mut(&mut self) -> &mut Option<Expr> {
+        &mut self.tail
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// A statement; the span includes any terminating semicolon.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Statement {
+    kind: StatementKind,
+    span: ByteSpan,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: ByteSpan) -> Statement {
+        Statement {
+            kind,
+            span,
+        }
+    }
+
+    pub fn kind(&self) -> &StatementKind {
+        &self.kind
+    }
+
+    pub fn kind_mut(&mut self) -> &mut StatementKind {
+        &mut self.kind
+    }
+
+    pub fn into_kind(self) -> StatementKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StatementKind {
+    Let(Let),
+    Assignment(Assignment),
+    Block(Block),
+    Expr(Expr),
+    For(For),
+    While(While),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+}
+
+/// A new binding, e.g. `let mut x: f32 = y;`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Let {
+    binding: Identifier,
+    mutable: bool,
+    type_name: Option<TypeName>,
+    expression: Option<Expr>,
+    span: ByteSpan,
+}
+
+impl Let {
+    pub fn new(binding: Identifier, mutable: bool, type_name: Option<TypeName>, expression: Option<Expr>, span: ByteSpan) -> Let {
+        Let {
+            binding,
+            mutable,
+            type_name,
+            expression,
+            span,
+        }
+    }
+
+    pub fn binding(&self) -> &Identifier {
+        &self.binding
+    }
+
+    pub fn mutable(&self) -> bool {
+        self.mutable
+    }
+
+    pub fn type_name(&self) -> Option<&TypeName> {
+        self.type_name.as_ref()
+    }
+
+    pub fn expression(&self) -> Option<&Expr> {
+        self.expression.as_ref()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// A loop over an integer range, e.g. `for i in 0..N { ... }`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct For {
+    binding: Identifier,
+    range: Range,
+    body: Block,
+    span: ByteSpan,
+}
+
+impl For {
+    pub fn new(binding: Identifier, range: Range, body: Block, span: ByteSpan) -> For {
+        For {
+            binding,
+            range,
+            body,
+            span,
+        }
+    }
+
+    pub fn binding(&self) -> &Identifier {
+        &self.binding
+    }
+
+    pub fn range(&self) -> &Range {
+        &self.range
+    }
+
+    pub fn body(&self) -> &Block {
+        &self.body
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// `start..end`, or `start..=end` when inclusive.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Range {
+    start: Expr,
+    end: Expr,
+    inclusive: bool,
+    span: ByteSpan,
+}
+
+impl Range {
+    pub fn new(start: Expr, end: Expr, inclusive: bool, span: ByteSpan) -> Range {
+        Range {
+            start,
+            end,
+            inclusive,
+            span,
+        }
+    }
+
+    pub fn start(&self) -> &Expr {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Expr {
+        &self.end
+    }
+
+    pub fn inclusive(&self) -> bool {
+        self.inclusive
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct While {
+    condition: Expr,
+    body: Block,
+    span: ByteSpan,
+}
+
+impl While {
+    pub fn new(condition: Expr, body: Block, span: ByteSpan) -> While {
+        While {
+            condition,
+            body,
+            span,
+        }
+    }
+
+    pub fn condition(&self) -> &Expr {
+        &self.condition
+    }
+
+    pub fn body(&self) -> &Block {
+        &self.body
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+/// `if`, usable both as a statement and as an expression.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct If {
+    condition: Expr,
+    then: Block,
+    otherwise: Option<Else>,
+    span: ByteSpan,
+}
+
+impl If {
+    pub fn new(condition: Expr, then: Block, otherwise: Option<Else>, span: ByteSpan) -> If {
+        If {
+            condition,
+            then,
+            otherwise,
+            span,
+        }
+    }
+
+    pub fn condition(&self) -> &Expr {
+        &self.condition
+    }
+
+    pub fn then(&self) -> &Block {
+        &self.then
+    }
+
+    pub fn otherwise(&self) -> Option<&Else> {
+        self.otherwise.as_ref()
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Else {
+    If(Box<If>),
+    Block(Block),
+}
+
+impl Else {
+    pub fn span(&self) -> ByteSpan {
+        match self {
+            Else::If(if_expr) => if_expr.span,
+            Else::Block(block) => block.span,
+        }
+    }
+}
+
+/// Reassignment of an existing place, e.g. `x = y;` or `v.x += 1;`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Assignment {
+    target: Expr,
+    /// The operator of a compound assignment, e.g. `Add` for `+=`.
+    operator: Option<BinaryOp>,
+    expression: Expr,
+    span: ByteSpan,
+}
+
+impl Assignment {
+    pub fn new(target: Expr, operator: Option<BinaryOp>, expression: Expr, span: ByteSpan) -> Assignment {
+        Assignment {
+            target,
+            operator,
+            expression,
+            span,
+        }
+    }
+
+    pub fn target(&self) -> &Expr {
+        &self.target
+    }
+
+    pub fn operator(&self) -> Option<BinaryOp> {
+        self.operator
+    }
+
+    pub fn expression(&self) -> &Expr {
+        &self.expression
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Expr {
+    kind: ExprKind,
+    span: ByteSpan,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExprKind {
+    Literal(Literal),
+    Identifier(Identifier),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// A parenthesized expression.
+    Group(Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    /// Field access, which includes swizzles such as `v.xyz`.
+    Field(Box<Expr>, Identifier),
+    Index(Box<Expr>, Box<Expr>),
+    /// Struct construction, e.g. `Material { albedo: a, roughness: r }`
+    Struct(Identifier, Vec<(Identifier, Expr)>),
+    If(Box<If>),
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: ByteSpan) -> Expr {
+        Expr {
+            kind,
+            span,
+        }
+    }
+
+    pub fn kind(&self) -> &ExprKind {
+        &self.kind
+    }
+
+    pub fn kind_mut(&mut self) -> &mut ExprKind {
+        &mut self.kind
+    }
+
+    pub fn into_kind(self) -> ExprKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+
+    /// Whether this expression may appear on the left of an assignment.
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            ExprKind::Identifier(_) => true,
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => base.is_place(),
+            ExprKind::Group(inner) => inner.is_place(),
+            _ => false,
+        }
+    }
+
+    /// The components of a swizzle (e.g. `xyz` in `v.xyz`), if this is a
+    /// field access that could be one.
+    pub fn swizzle(&self) -> Option<&str> {
+        const SETS: [&str; 3] = ["xyzw", "rgba", "stpq"];
+        let field = match &self.kind {
+            ExprKind::Field(_, field) => field.str(),
+            _ => return None,
+        };
+        if field.is_empty() || field.len() > 4 {
+            return None;
+        }
+        let is_swizzle = SETS.iter()
+            .any(|set| field.chars().all(|c| set.contains(c)));
+        if is_swizzle {
+            Some(field)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+impl UnaryOp {
+    fn from_token(token: &Token) -> Option<UnaryOp> {
+        match token {
+            Token::Minus => Some(UnaryOp::Negate),
+            Token::Bang => Some(UnaryOp::Not),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl BinaryOp {
+    fn from_token(token: &Token) -> Option<BinaryOp> {
+        let op = match token {
+            Token::Plus => BinaryOp::Add,
+            Token::Minus => BinaryOp::Subtract,
+            Token::Star => BinaryOp::Multiply,
+            Token::Slash => BinaryOp::Divide,
+            Token::Percent => BinaryOp::Remainder,
+            Token::Ampersand => BinaryOp::BitAnd,
+            Token::Pipe => BinaryOp::BitOr,
+            Token::Caret => BinaryOp::BitXor,
+            Token::ShiftLeft => BinaryOp::ShiftLeft,
+            Token::ShiftRight => BinaryOp::ShiftRight,
+            Token::And => BinaryOp::And,
+            Token::Or => BinaryOp::Or,
+            Token::EqualsEquals => BinaryOp::Equal,
+            Token::NotEquals => BinaryOp::NotEqual,
+            Token::LessThan => BinaryOp::Less,
+            Token::LessThanEquals => BinaryOp::LessEqual,
+            Token::GreaterThan => BinaryOp::Greater,
+            Token::GreaterThanEquals => BinaryOp::GreaterEqual,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// The operator of a compound assignment token such as `+=`.
+    fn from_assignment_token(token: &Token) -> Option<BinaryOp> {
+        let op = match token {
+            Token::PlusEquals => BinaryOp::Add,
+            Token::MinusEquals => BinaryOp::Subtract,
+            Token::StarEquals => BinaryOp::Multiply,
+            Token::SlashEquals => BinaryOp::Divide,
+            Token::PercentEquals => BinaryOp::Remainder,
+            Token::AmpersandEquals => BinaryOp::BitAnd,
+            Token::PipeEquals => BinaryOp::BitOr,
+            Token::CaretEquals => BinaryOp::BitXor,
+            Token::ShiftLeftEquals => BinaryOp::ShiftLeft,
+            Token::ShiftRightEquals => BinaryOp::ShiftRight,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Left and right binding power; higher binds tighter.
+    fn binding_power(&self) -> (u8, u8) {
+        let precedence = match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Equal | BinaryOp::NotEqual
+                | BinaryOp::Less | BinaryOp::LessEqual
+                | BinaryOp::Greater | BinaryOp::GreaterEqual => 3,
+            BinaryOp::BitOr => 4,
+            BinaryOp::BitXor => 5,
+            BinaryOp::BitAnd => 6,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 7,
+            BinaryOp::Add | BinaryOp::Subtract => 8,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => 9,
+        };
+        // all binary operators are left associative
+        (precedence * 2, precedence * 2 + 1)
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(self,
+            BinaryOp::Equal | BinaryOp::NotEqual
+                | BinaryOp::Less | BinaryOp::LessEqual
+                | BinaryOp::Greater | BinaryOp::GreaterEqual)
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Remainder => "%",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+        }
+    }
+}
+
+/// Unary operators bind tighter than any binary operator.
+const PREFIX_BINDING_POWER: u8 = 20;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Literal {
+    value: LiteralValue,
+    /// The literal as written, e.g. `0xFFu`.
+    text: String,
+    span: ByteSpan,
+}
+
+impl Literal {
+    pub fn new(value: LiteralValue, text: String, span: ByteSpan) -> Literal {
+        Literal {
+            value,
+            text,
+            span,
+        }
+    }
+
+    pub fn value(&self) -> &LiteralValue {
+        &self.value
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn span(&self) -> ByteSpan {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LiteralValue {
+    Bool(bool),
+    Integer(u64, Option<NumberSuffix>),
+    Float(f64, Option<NumberSuffix>),
+    /// String contents with escapes resolved.
+    String(String),
+}
+
+/// An explicit type on a numeric literal, e.g. `u` in `3u` or `f32` in `1.0f32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum NumberSuffix {
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl NumberSuffix {
+    const INTEGER: [(&'static str, NumberSuffix); 4] = [
+        ("u32", NumberSuffix::U32),
+        ("i32", NumberSuffix::I32),
+        ("u", NumberSuffix::U32),
+        ("i", NumberSuffix::I32),
+    ];
+
+    const FLOAT: [(&'static str, NumberSuffix); 4] = [
+        ("f32", NumberSuffix::F32),
+        ("f64", NumberSuffix::F64),
+        ("lf", NumberSuffix::F64),
+        ("f", NumberSuffix::F32),
+    ];
+
+    /// Split a suffix from the end of a numeric literal, trying longer
+    /// suffixes first.
+    fn split<'a>(text: &'a str, suffixes: &[(&str, NumberSuffix)]) -> (&'a str, Option<NumberSuffix>) {
+        for (suffix, value) in suffixes {
+            if let Some(number) = text.strip_suffix(suffix) {
+                return (number, Some(*value));
+            }
+        }
+        (text, None)
+    }
+}
+
+pub fn parse_module(source: &str) -> std::result::Result<Module, CompilerError> {
+    let (module, mut errors) = module(&cst::parse(source));
+    if !errors.is_empty() {
+        return Err(errors.remove(0).into());
+    }
+
+    Ok(module)
+}
+
+/// Parse a whole source file, locating every error within it.
+pub fn parse_file(source: &Arc<SourceFile>) -> std::result::Result<Module, CompilerError> {
+    let (module, errors) = parse_file_partial(source);
+    if !errors.is_empty() {
+        return Err(CompilerError::shaders(errors));
+    }
+
+    Ok(module)
+}
+
+/// Parse as much of a source file as possible. Items which fail to parse are
+/// kept as `ItemKind::Error`, with one error for each.
+pub fn parse_file_partial(source: &Arc<SourceFile>) -> (Module, Vec<ShaderError>) {
+    let (module, errors) = module(&cst::parse(source.text()));
+    let errors = errors.iter()
+        .map(|err| ShaderError::syntax(err, source))
+        .collect();
+    (module, errors)
+}
+
+/// Build the module for a syntax tree, parsing each of its item nodes. A
+/// malformed item does not stop the parse: it is recorded as
+/// `ItemKind::Error` and parsing resumes with the next node.
+pub fn module(tree: &SyntaxNode) -> (Module, Vec<SyntaxError>) {
+    let parsed = ParsedModule::new(tree.clone());
+    let errors = parsed.errors();
+    (Arc::unwrap_or_clone(parsed.module), errors)
+}
+
+/// A module along with the syntax tree it was built from, which can be
+/// edited without parsing the whole module again.
+#[derive(Debug, Clone)]
+pub struct ParsedModule {
+    tree: SyntaxNode,
+    module: Arc<Module>,
+    /// The error for each item which failed to parse, in item order.
+    item_errors: Vec<Option<SyntaxError>>,
+    next_id: u32,
+}
+
+/// The items replaced by an edit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemChanges {
+    removed: Vec<ItemId>,
+    added: Vec<ItemId>,
+}
+
+impl ItemChanges {
+    pub fn removed(&self) -> &[ItemId] {
+        &self.removed
+    }
+
+    pub fn added(&self) -> &[ItemId] {
+        &self.added
+    }
+}
+
+impl ParsedModule {
+    pub fn new(tree: SyntaxNode) -> ParsedModule {
+        let mut parsed = ParsedModule {
+            tree,
+            module: Arc::default(),
+            item_errors: Vec::new(),
+            next_id: 0,
+        };
+        let count = parsed.tree.child_nodes().iter().filter(|node| node.kind() == NodeKind::Item).count();
+        let mut module = Module::default();
+        for (item, error) in parsed.parse_items(0..count) {
+            module.ids.push(parsed.allocate_id());
+            module.items.push(item);
+            parsed.item_errors.push(error);
+        }
+        module.span = parsed.span(&module.items);
+        parsed.module = Arc::new(module);
+        parsed
+    }
+
+    pub fn tree(&self) -> &SyntaxNode {
+        &self.tree
+    }
+
+    pub fn module(&self) -> &Arc<Module> {
+        &self.module
+    }
+
+    /// Every syntax error in the module.
+    pub fn errors(&self) -> Vec<SyntaxError> {
+        let mut errors: Vec<SyntaxError> = self.item_errors.iter().flatten().cloned().collect();
+        if let Some(open) = unterminated_comment(&self.tree) {
+            // anything expected at end of file was swallowed by the comment
+            let end = self.tree.span().end;
+            errors.retain(|err| err.span().start < end);
+            errors.push(SyntaxError {
+                message: "unterminated block comment".to_owned(),
+                found: None,
+                span: open,
+            });
+        }
+        errors
+    }
+
+    /// Apply an edit to the source. Only the items which the edit touches
+    /// are parsed again; the others keep their IDs, and are moved if the
+    /// edit comes before them.
+    pub fn edit(&mut self, edit: &TextEdit) -> ItemChanges {
+        let tree = cst::reparse(&self.tree, edit);
+        let old = item_greens(&self.tree);
+        let new = item_greens(&tree);
+        let same = |(old, new): &(&Arc<GreenNode>, &Arc<GreenNode>)| Arc::ptr_eq(old, new);
+        let mut prefix = old.iter().zip(&new).take_while(same).count();
+        let suffix = old.iter().rev().zip(new.iter().rev())
+            .take(old.len().min(new.len()) - prefix)
+            .take_while(same)
+            .count();
+        // a failed item's error may name the token after it
+        if prefix > 0 && self.item_errors[prefix - 1].is_some() {
+            prefix -= 1;
+        }
+        self.tree = tree;
+
+        let Module { mut items, mut ids, .. } = Arc::unwrap_or_clone(std::mem::take(&mut self.module));
+        let kept = old.len() - suffix;
+        let delta = edit.delta();
+        let mut tail_items: Vec<Item> = items.drain(kept..).collect();
+        let tail_ids: Vec<ItemId> = ids.drain(kept..).collect();
+        let mut tail_errors: Vec<Option<SyntaxError>> = self.item_errors.drain(kept..).collect();
+        let mut shift = Shift::new(delta);
+        for item in &mut tail_items {
+            shift.visit_item_mut(item);
+        }
+        for error in tail_errors.iter_mut().flatten() {
+            shift.visit_span_mut(&mut error.span);
+        }
+
+        items.truncate(prefix);
+        self.item_errors.truncate(prefix);
+        let mut changes = ItemChanges {
+            removed: ids.split_off(prefix),
+            added: Vec::new(),
+        };
+        for (item, error) in self.parse_items(prefix..new.len() - suffix) {
+            let id = self.allocate_id();
+            changes.added.push(id);
+            ids.push(id);
+            items.push(item);
+            self.item_errors.push(error);
+        }
+        items.append(&mut tail_items);
+        ids.extend(tail_ids);
+        self.item_errors.append(&mut tail_errors);
+
+        let span = self.span(&items);
+        self.module = Arc::new(Module { items, ids, span });
+        changes
+    }
+
+    /// Parse the item nodes in `range`, each with the error it failed with.
+    fn parse_items(&self, range: std::ops::Range<usize>) -> Vec<(Item, Option<SyntaxError>)> {
+        let source = self.tree.text();
+        let children = self.tree.children();
+        let items: Vec<usize> = children.iter()
+            .enumerate()
+            .filter(|(_, child)| child.kind() == Some(NodeKind::Item))
+            .map(|(i, _)| i)
+            .collect();
+
+        items[range].iter()
+            .map(|&i| {
+                let node = match &children[i] {
+                    SyntaxElement::Node(node) => node,
+                    SyntaxElement::Token(_) => unreachable!("item nodes are nodes"),
+                };
+                // errors at the end of an item can name the token which
+                // follows it
+                let mut buffer = lexed(&children[i]);
+                let next = children[i + 1..].iter()
+                    .flat_map(SyntaxElement::tokens)
+                    .find(|token| !token.is_trivia());
+                buffer.extend(next.map(|token| (token.kind().clone(), token.span().range())));
+                match item_node(node, TokenStream::new(&buffer, &source)) {
+                    Ok(item) => (item, None),
+                    Err(err) => {
+                        let item = Item {
+                            kind: ItemKind::Error,
+                            visibility: Visibility::Private,
+                            docs: Vec::new(),
+                            attributes: Vec::new(),
+                            span: node.significant_span().unwrap_or_else(|| node.span()),
+                        };
+                        (item, Some(SyntaxError::from(&err)))
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn allocate_id(&mut self) -> ItemId {
+        let id = ItemId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// The module spans from its first item to the end of the source.
+    fn span(&self, items: &[Item]) -> ByteSpan {
+        let end = self.tree.span().end;
+        let start = items.first().map(|item| item.span.start).unwrap_or(end);
+        ByteSpan::new(start, end)
+    }
+}
+
+/// The green node of each item in a module's syntax tree.
+fn item_greens(tree: &SyntaxNode) -> Vec<Arc<GreenNode>> {
+    tree.green()
+        .children()
+        .iter()
+        .filter_map(|child| match child {
+            GreenElement::Node(node) if node.kind() == NodeKind::Item => Some(node.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The span of the `/*` opening a comment which is never closed. Such a
+/// comment is always the last thing in the tree.
+fn unterminated_comment(tree: &SyntaxNode) -> Option<ByteSpan> {
+    let last = tree.children().pop().filter(|last| last.kind() == Some(NodeKind::Comment))?;
+    let start = last.span().start;
+    match lexed(&last).first() {
+        Some((Token::BlockComment(false), _)) => Some(ByteSpan::new(start, start + "/*".len())),
+        _ => None,
+    }
+}
+
+/// The tokens of a syntax element which the parser needs, with their spans.
+fn lexed(element: &SyntaxElement) -> Vec<(Token, Span)> {
+    element.tokens()
+        .into_iter()
+        .filter(|token| *token.kind() != Token::Whitespace)
+        .map(|token| (token.kind().clone(), token.span().range()))
+        .collect()
+}
+
+/// Parse an item node, given its tokens and the token after it. The item
+/// must cover exactly the node's tokens.
+fn item_node<'source>(node: &SyntaxNode, tokens: TokenStream<'source>) -> std::result::Result<Item, ParseError<'source>> {
+    let (rest, item) = item(tokens)?;
+    let end = node.significant_span().map(|span| span.end);
+    if Some(rest.prev_end()) != end {
+        return Err(ParseError::syntax(rest, "expected item"));
+    }
+    Ok(item)
+}
+
+pub fn item(mut tokens: TokenStream) -> Result<Item> {
+    let start = tokens.position();
+    let mut docs = Vec::new();
+    let mut attributes = Vec::new();
+    loop {
+        docs.extend(doc_comments(&tokens));
+        if tokens.peek() != Some(&Token::Pound) {
+            break;
+        }
+        let (rest, attribute) = attribute(tokens)?;
+        tokens = rest;
+        attributes.push(attribute);
+    }
+
+    let visibility = match tokens.peek() {
+        Some(Token::Pub) => {
+            tokens.next();
+            Visibility::Public
+        },
+        _ => Visibility::Private,
+    };
+
+    let (tokens, kind) = item_kind(tokens)?;
+    let span = span_from(start, &tokens);
+    Ok((tokens, Item { kind, visibility, docs, attributes, span }))
+}
+
+/// The doc comments ahead of the next token.
+fn doc_comments(tokens: &TokenStream) -> Vec<Doc> {
+    tokens.comments()
+        .into_iter()
+        .filter_map(|span| Doc::parse(&tokens.source()[span.clone()], span.into()))
+        .collect()
+}
+
+pub fn attribute(mut tokens: TokenStream) -> Result<Attribute> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Pound, Token::LeftBracket)?;
+    let name = expect_identifier!(tokens);
+    let arguments = match tokens.peek() {
+        Some(Token::LeftParen) => {
+            tokens.next();
+            let (rest, arguments) = call_arguments(tokens)?;
+            tokens = rest;
+            Some(arguments)
+        },
+        _ => None,
+    };
+    tokens = expect_sequence!(tokens, Token::RightBracket)?;
+    let span = span_from(start, &tokens);
+    Ok((tokens, Attribute { name, arguments, span }))
+}
+
+pub fn item_kind(tokens: TokenStream) -> Result<ItemKind> {
+    let upcoming = tokens.peek();
+    if let Some(intention) = upcoming {
+        match intention {
+            Token::Use => {
+                return use_item(tokens.clone()).map(|(s, u)| (s, ItemKind::Use(u)));
+            },
+            Token::Declare => {
+                return declare_item(tokens.clone());
+            },
+            Token::Uniform | Token::In | Token::Out | Token::Const => {
+                return global(tokens.clone());
+            },
+            Token::Function => {
+                return function(tokens.clone());
+            },
+            Token::Struct => {
+                return struct_item(tokens.clone()).map(|(s, st)| (s, ItemKind::Struct(st)));
+            },
+            _ => {},
+        }
+    }
+    Err(ParseError::syntax(tokens, "expected item"))
+}
+
+pub fn use_item(mut tokens: TokenStream) -> Result<Use> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Use)?;
+
+    let root = match tokens.peek() {
+        Some(Token::Crate) => {
+            tokens = expect_sequence!(tokens, Token::Crate, Token::PathSeparator)?;
+            PathRoot::Crate
+        },
+        Some(Token::Super) => {
+            let mut depth = 0;
+            while let Some(Token::Super) = tokens.peek() {
+                tokens = expect_sequence!(tokens, Token::Super, Token::PathSeparator)?;
+                depth += 1;
+            }
+            PathRoot::Super(depth)
+        },
+        _ => PathRoot::Source,
+    };
+
+    let (rest, tree) = use_tree(tokens)?;
+    tokens = expect_sequence!(rest, Token::Semicolon)?;
+    let span = span_from(start, &tokens);
+    Ok((tokens, Use { root, tree, span }))
+}
+
+pub fn use_tree(mut tokens: TokenStream) -> Result<UseTree> {
+    let start = tokens.position();
+    let mut path = Vec::new();
+    let kind = loop {
+        match tokens.peek() {
+            Some(Token::Star) => {
+                tokens.next();
+                break UseTreeKind::Glob;
+            },
+            Some(Token::LeftBrace) => {
+                tokens.next();
+                let mut trees = Vec::new();
+                loop {
+                    if let Some(Token::RightBrace) = tokens.peek() {
+                        tokens.next();
+                        break;
+                    }
+                    let (rest, tree) = use_tree(tokens)?;
+                    tokens = rest;
+                    trees.push(tree);
+                    match tokens.peek() {
+                        Some(Token::Comma) => {
+                            tokens.next();
+                        },
+                        Some(Token::RightBrace) => {},
+                        _ => return Err(ParseError::syntax(tokens, "expected `,` or `}`")),
+                    }
+                }
+                break UseTreeKind::Group(trees);
+            },
+            _ => {},
+        }
+
+        path.push(expect_identifier!(tokens));
+        match tokens.peek() {
+            Some(Token::PathSeparator) => {
+                tokens.next();
+            },
+            Some(Token::As) => {
+                tokens.next();
+                break UseTreeKind::Simple(Some(expect_identifier!(tokens)));
+            },
+            _ => break UseTreeKind::Simple(None),
+        }
+    };
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, UseTree { path, kind, span }))
+}
+
+pub fn declare_item(tokens: TokenStream) -> Result<ItemKind> {
+    let into_item = |(tokens, d)| (tokens, ItemKind::Declare(d));
+    return_if!(declare_type(tokens.clone()).map(into_item));
+    return_if!(declare_const(tokens.clone()).map(into_item));
+    return_if!(declare_function(tokens.clone()).map(into_item));
+    Err(ParseError::syntax(tokens, "expected declaration"))
+}
+
+pub fn declare_type(mut tokens: TokenStream) -> Result<Declare> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Declare, Token::Type)?;
+    let name = expect_identifier!(tokens);
+    // let (mut tokens, name) = match type_name(tokens)? {
+    //     (tokens, TypeName::Identifier(ident)) => (tokens, ident),
+    //     _ => unimplemented!("whoops"),
+    // };
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+    let span = span_from(start, &tokens);
+    Ok((tokens, Declare::Type(DeclareType { name, span })))
+}
+
+pub fn declare_const(mut tokens: TokenStream) -> Result<Declare> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Declare, Token::Const)?;
+    let name = expect_identifier!(tokens);
+
+    tokens = expect_sequence!(tokens, Token::Colon)?;
+    let (mut tokens, type_name) = type_name(tokens)?;
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, Declare::Const(DeclareConst { name, type_name, span })))
+}
+
+/// An extern function signature, e.g. `declare fn shade(scene: Scene) -> vec4;`
+pub fn declare_function(mut tokens: TokenStream) -> Result<Declare> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Declare, Token::Function)?;
+    let name = expect_identifier!(tokens);
+
+    let (mut tokens, arguments) = self::arguments(tokens)?;
+
+    // omitting the return type declares a function returning the unit type
+    let end = tokens.prev_end();
+    let mut return_type = TypeName {
+        kind: TypeNameKind::Tuple(Vec::new()),
+        span: ByteSpan::new(end, end),
+    };
+    if let Some(Token::RightArrow) = tokens.peek() {
+        tokens.next();
+        let result = type_name(tokens)?;
+        tokens = result.0;
+        return_type = result.1;
+    }
+
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+
+    let function = DeclareFunction {
+        name,
+        arguments,
+        return_type,
+        span: span_from(start, &tokens),
+    };
+
+    Ok((tokens, Declare::Function(function)))
+}
+
+/// A parenthesized parameter list, e.g. `(scene: Scene, material: Material)`
+pub fn arguments(mut tokens: TokenStream) -> Result<Arguments> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::LeftParen)?;
+
+    let mut arguments = Vec::new();
+    loop {
+        if let Some(Token::RightParen) = tokens.peek() {
+            tokens.next();
+            break;
+        }
+
+        let name = expect_identifier!(tokens);
+        tokens = expect_sequence!(tokens, Token::Colon)?;
+        let (rest, definition) = type_name(tokens)?;
+        tokens = rest;
+        arguments.push((name, definition));
+
+        match tokens.peek() {
+            Some(Token::Comma) => {
+                tokens.next();
+            },
+            Some(Token::RightParen) => {
+                tokens.next();
+                break;
+            },
+            _ => return Err(ParseError::syntax(tokens, "expected `,` or `)`")),
+        }
+    }
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, Arguments { arguments, span }))
+}
+
+pub fn type_name(tokens: TokenStream) -> Result<TypeName> {
+    let (tokens, (type_name, _)) = nested_type_name(tokens, 0)?;
+    Ok((tokens, type_name))
+}
+
+/// Parse a type within `depth` generic argument lists. The flag is set when
+/// the type's own argument list was closed by a `>>`, which also closes the
+/// enclosing list.
+fn nested_type_name(mut tokens: TokenStream, depth: usize) -> Result<(TypeName, bool)> {
+    let start = tokens.position();
+    let (kind, closes_parent) = match tokens.peek() {
+        Some(Token::LeftBracket) => {
+            tokens.next();
+            let (rest, element) = type_name(tokens)?;
+            tokens = rest;
+            let length = match tokens.peek() {
+                Some(Token::Semicolon) => {
+                    tokens.next();
+                    let (rest, length) = expression(tokens)?;
+                    tokens = rest;
+                    Some(Box::new(length))
+                },
+                _ => None,
+            };
+            tokens = expect_sequence!(tokens, Token::RightBracket)?;
+            (TypeNameKind::Array(Box::new(element), length), false)
+        },
+        Some(Token::LeftParen) => {
+            tokens.next();
+            let mut elements = Vec::new();
+            let mut trailing_comma = false;
+            loop {
+                if let Some(Token::RightParen) = tokens.peek() {
+                    tokens.next();
+                    break;
+                }
+                let (rest, element) = type_name(tokens)?;
+                tokens = rest;
+                elements.push(element);
+                trailing_comma = false;
+                match tokens.peek() {
+                    Some(Token::Comma) => {
+                        tokens.next();
+                        trailing_comma = true;
+                    },
+                    Some(Token::RightParen) => {},
+                    _ => return Err(ParseError::syntax(tokens, "expected `,` or `)`")),
+                }
+            }
+            // `(T)` is just a parenthesised `T`, while `(T,)` is a tuple
+            if elements.len() == 1 && !trailing_comma {
+                let mut element = elements.remove(0);
+                element.span = span_from(start, &tokens);
+                return Ok((tokens, (element, false)));
+            }
+            (TypeNameKind::Tuple(elements), false)
+        },
+        Some(Token::Integer) if depth > 0 => {
+            let (rest, literal) = literal(tokens)?;
+            tokens = rest;
+            (TypeNameKind::Literal(literal), false)
+        },
+        _ => {
+            let name = expect_identifier!(tokens);
+            if tokens.peek() == Some(&Token::LessThan) {
+                let (rest, (arguments, closes_parent)) = generic_arguments(tokens, depth)?;
+                tokens = rest;
+                (TypeNameKind::Generic(name, arguments), closes_parent)
+            } else {
+                (TypeNameKind::Identifier(name), false)
+            }
+        },
+    };
+
+    let mut span = span_from(start, &tokens);
+    if closes_parent {
+        // the last `>` of the shared `>>` belongs to the enclosing list
+        span.end -= 1;
+    }
+    Ok((tokens, (TypeName { kind, span }, closes_parent)))
+}
+
+/// Parse `<A, B>`, splitting a closing `>>` when nested in another list.
+fn generic_arguments(mut tokens: TokenStream, depth: usize) -> Result<(Vec<TypeName>, bool)> {
+    tokens = expect_sequence!(tokens, Token::LessThan)?;
+    let mut arguments = Vec::new();
+    loop {
+        let (rest, (argument, closed)) = nested_type_name(tokens, depth + 1)?;
+        tokens = rest;
+        arguments.push(argument);
+        if closed {
+            return Ok((tokens, (arguments, false)));
+        }
+
+        match tokens.peek() {
+            Some(Token::Comma) => {
+                tokens.next();
+            },
+            Some(Token::GreaterThan) => {
+                tokens.next();
+                return Ok((tokens, (arguments, false)));
+            },
+            Some(Token::ShiftRight) if depth > 0 => {
+                tokens.next();
+                return Ok((tokens, (arguments, true)));
+            },
+            _ => return Err(ParseError::syntax(tokens, "expected `,` or `>`")),
+        }
+    }
+}
+
+pub fn global(mut tokens: TokenStream) -> Result<ItemKind> {
+    const MSG: &str = "expected global qualifier (in, out, uniform or const)";
+    let start = tokens.position();
+
+    let qualifier = match tokens.peek() {
+        Some(Token::In) => GlobalQualifier::In,
+        Some(Token::Out) => GlobalQualifier::Out,
+        Some(Token::Uniform) => GlobalQualifier::Uniform,
+        Some(Token::Const) => GlobalQualifier::Const,
+        _ => {
+            return Err(ParseError::syntax(tokens, MSG))
+        },
+    };
+    tokens.next();
+
+    let identifier = expect_identifier!(tokens);
+    tokens = expect_sequence!(tokens, Token::Colon)?;
+
+    let (mut tokens, definition) = type_name(tokens)?;
+    let initializer = match qualifier {
+        GlobalQualifier::Const => {
+            tokens = expect_sequence!(tokens, Token::Equals)?;
+            let (rest, initializer) = expression(tokens)?;
+            tokens = rest;
+            Some(initializer)
+        },
+        _ => None,
+    };
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+
+    let global = Global {
+        qualifier,
+        identifier,
+        definition,
+        initializer,
+        span: span_from(start, &tokens),
+    };
+
+    Ok((tokens, ItemKind::Global(global)))
+}
+
+pub fn function(mut tokens: TokenStream) -> Result<ItemKind> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Function)?;
+
+    let name = expect_identifier!(tokens);
+
+    let (mut tokens, arguments) = self::arguments(tokens)?;
+
+    let mut return_type = None;
+    match tokens.peek() {
+        Some(Token::LeftBrace) => {},
+        _ => {
             tokens = expect_sequence!(tokens, Token::RightArrow)?;
             let result = type_name(tokens)?;
             tokens = result.0;
-            return_type = Some(result.1);
+            return_type = Some(result.1);
+        },
+    }
+
+    let (tokens, body) = block(tokens)?;
+
+    let function = Function {
+        name,
+        arguments,
+        body,
+        return_type,
+        span: span_from(start, &tokens),
+    };
+
+    Ok((tokens, ItemKind::Function(function)))
+}
+
+pub fn struct_item(mut tokens: TokenStream) -> Result<Struct> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Struct)?;
+    let name = expect_identifier!(tokens);
+    let open = tokens.clone();
+    tokens = expect_sequence!(tokens, Token::LeftBrace)?;
+
+    let mut fields = Vec::new();
+    loop {
+        if let Some(Token::RightBrace) = tokens.peek() {
+            tokens.next();
+            break;
+        }
+
+        let field = expect_identifier!(tokens);
+        tokens = expect_sequence!(tokens, Token::Colon)?;
+        let (rest, definition) = type_name(tokens)?;
+        tokens = rest;
+        fields.push((field, definition));
+
+        match tokens.peek() {
+            Some(Token::Comma) => {
+                tokens.next();
+            },
+            Some(Token::RightBrace) => {
+                tokens.next();
+                break;
+            },
+            _ => return Err(ParseError::syntax(tokens, "expected `,` or `}`")),
+        }
+    }
+
+    if fields.is_empty() {
+        let braces = ByteSpan::from(open.span().unwrap()).to(tokens.span_prev().unwrap().into());
+        return Err(ParseError::syntax(open, "structs must have at least one field").at(braces));
+    }
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, Struct { name, fields, span }))
+}
+
+/// Either a complete statement, or the trailing expression of a block.
+enum BlockEntry {
+    Statement(Box<Statement>),
+    Tail(Expr),
+}
+
+pub fn block(mut tokens: TokenStream) -> Result<Block> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::LeftBrace)?;
+
+    let mut statements = Vec::new();
+    let mut tail = None;
+    loop {
+        match tokens.peek() {
+            Some(Token::RightBrace) => {
+                tokens.next();
+                break;
+            },
+            None => {
+                return Err(ParseError::syntax(tokens, "expected `}`"));
+            },
+            _ => {},
+        }
+
+        let result = block_entry(tokens)?;
+        tokens = result.0;
+        match result.1 {
+            BlockEntry::Statement(statement) => statements.push(*statement),
+            BlockEntry::Tail(expr) => {
+                // block_entry only yields a tail when `}` is next
+                tokens = expect_sequence!(tokens, Token::RightBrace)?;
+                tail = Some(expr);
+                break;
+            },
+        }
+    }
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, Block { statements, tail, span }))
+}
+
+pub fn statement(tokens: TokenStream) -> Result<Statement> {
+    match block_entry(tokens)? {
+        (tokens, BlockEntry::Statement(statement)) => Ok((tokens, *statement)),
+        (tokens, BlockEntry::Tail(_)) => Err(ParseError::syntax(tokens, "expected `;`")),
+    }
+}
+
+fn block_entry(tokens: TokenStream) -> Result<BlockEntry> {
+    let start = tokens.position();
+
+    match tokens.peek() {
+        Some(Token::Let) => {
+            return let_statement(tokens.clone())
+                .map(|(s, l)| into_entry(start, s, StatementKind::Let(l)));
+        },
+        Some(Token::LeftBrace) => {
+            return block(tokens.clone())
+                .map(|(s, b)| into_entry(start, s, StatementKind::Block(b)));
+        },
+        Some(Token::For) => {
+            return for_statement(tokens.clone())
+                .map(|(s, f)| into_entry(start, s, StatementKind::For(f)));
+        },
+        Some(Token::While) => {
+            return while_statement(tokens.clone())
+                .map(|(s, w)| into_entry(start, s, StatementKind::While(w)));
+        },
+        Some(Token::Return) => {
+            return return_statement(tokens.clone())
+                .map(|(s, r)| (s, BlockEntry::Statement(Box::new(r))));
+        },
+        Some(Token::Break) => {
+            let tokens = tokens.clone();
+            let tokens = expect_sequence!(tokens, Token::Break, Token::Semicolon)?;
+            return Ok(into_entry(start, tokens, StatementKind::Break));
+        },
+        Some(Token::Continue) => {
+            let tokens = tokens.clone();
+            let tokens = expect_sequence!(tokens, Token::Continue, Token::Semicolon)?;
+            return Ok(into_entry(start, tokens, StatementKind::Continue));
+        },
+        Some(Token::If) => {
+            // like a block, an `if` statement needs no semicolon
+            let (mut tokens, if_expr) = if_expression(tokens.clone())?;
+            let span = if_expr.span;
+            let expr = Expr::new(ExprKind::If(Box::new(if_expr)), span);
+            return match tokens.peek() {
+                Some(Token::RightBrace) => Ok((tokens, BlockEntry::Tail(expr))),
+                Some(Token::Semicolon) => {
+                    tokens.next();
+                    Ok(into_entry(start, tokens, StatementKind::Expr(expr)))
+                },
+                _ => Ok(into_entry(start, tokens, StatementKind::Expr(expr))),
+            };
+        },
+        _ => {},
+    }
+
+    let (mut tokens, expr) = expression(tokens)?;
+    match tokens.peek() {
+        Some(Token::Semicolon) => {
+            tokens.next();
+            Ok(into_entry(start, tokens, StatementKind::Expr(expr)))
+        },
+        Some(Token::RightBrace) => Ok((tokens, BlockEntry::Tail(expr))),
+        Some(token) if is_assignment_operator(token) => {
+            assignment_rest(tokens, expr)
+                .map(|(s, a)| into_entry(start, s, StatementKind::Assignment(a)))
+        },
+        _ => Err(ParseError::syntax(tokens, "expected `;`")),
+    }
+}
+
+fn into_entry(start: usize, tokens: TokenStream, kind: StatementKind) -> (TokenStream, BlockEntry) {
+    let span = span_from(start, &tokens);
+    (tokens, BlockEntry::Statement(Box::new(Statement { kind, span })))
+}
+
+fn is_assignment_operator(token: &Token) -> bool {
+    *token == Token::Equals || BinaryOp::from_assignment_token(token).is_some()
+}
+
+pub fn let_statement(mut tokens: TokenStream) -> Result<Let> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Let)?;
+
+    let mutable = if let Some(Token::Mut) = tokens.peek() {
+        tokens.next();
+        true
+    } else {
+        false
+    };
+
+    let binding = expect_identifier!(tokens);
+
+    let mut type_name = None;
+    if let Some(Token::Colon) = tokens.peek() {
+        tokens.next();
+        let result = self::type_name(tokens)?;
+        tokens = result.0;
+        type_name = Some(result.1);
+    }
+
+    let mut expression = None;
+    if let Some(Token::Equals) = tokens.peek() {
+        tokens.next();
+        let result = self::expression(tokens)?;
+        tokens = result.0;
+        expression = Some(result.1);
+    }
+
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+
+    let statement = Let {
+        binding,
+        mutable,
+        type_name,
+        expression,
+        span: span_from(start, &tokens),
+    };
+
+    Ok((tokens, statement))
+}
+
+pub fn for_statement(mut tokens: TokenStream) -> Result<For> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::For)?;
+    let binding = expect_identifier!(tokens);
+    tokens = expect_sequence!(tokens, Token::In)?;
+
+    let (mut tokens, range_start) = expression(tokens)?;
+    let inclusive = match tokens.peek() {
+        Some(Token::Range) => false,
+        Some(Token::RangeInclusive) => true,
+        _ => return Err(ParseError::syntax(tokens, "expected range (`..` or `..=`)")),
+    };
+    tokens.next();
+    let (tokens, range_end) = expression(tokens)?;
+
+    let range = Range {
+        span: range_start.span.to(range_end.span),
+        start: range_start,
+        end: range_end,
+        inclusive,
+    };
+
+    let (tokens, body) = block(tokens)?;
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, For { binding, range, body, span }))
+}
+
+pub fn while_statement(mut tokens: TokenStream) -> Result<While> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::While)?;
+    let (tokens, condition) = expression(tokens)?;
+    let (tokens, body) = block(tokens)?;
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, While { condition, body, span }))
+}
+
+pub fn return_statement(mut tokens: TokenStream) -> Result<Statement> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::Return)?;
+
+    let mut value = None;
+    if let Some(Token::Semicolon) = tokens.peek() {
+        tokens.next();
+    } else {
+        let (rest, result) = expression(tokens)?;
+        tokens = expect_sequence!(rest, Token::Semicolon)?;
+        value = Some(result);
+    }
+
+    let statement = Statement {
+        kind: StatementKind::Return(value),
+        span: span_from(start, &tokens),
+    };
+
+    Ok((tokens, statement))
+}
+
+pub fn if_expression(mut tokens: TokenStream) -> Result<If> {
+    let start = tokens.position();
+    tokens = expect_sequence!(tokens, Token::If)?;
+    let (tokens, condition) = expression(tokens)?;
+    let (mut tokens, then) = block(tokens)?;
+
+    let mut otherwise = None;
+    if let Some(Token::Else) = tokens.peek() {
+        tokens.next();
+        if let Some(Token::If) = tokens.peek() {
+            let result = if_expression(tokens)?;
+            tokens = result.0;
+            otherwise = Some(Else::If(Box::new(result.1)));
+        } else {
+            let result = block(tokens)?;
+            tokens = result.0;
+            otherwise = Some(Else::Block(result.1));
+        }
+    }
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, If { condition, then, otherwise, span }))
+}
+
+pub fn assignment(tokens: TokenStream) -> Result<Assignment> {
+    let (tokens, target) = expression(tokens)?;
+    assignment_rest(tokens, target)
+}
+
+/// Parse the remainder of an assignment after its target.
+fn assignment_rest(mut tokens: TokenStream, target: Expr) -> Result<Assignment> {
+    if !target.is_place() {
+        return Err(ParseError::syntax(tokens, "invalid left hand side of assignment"));
+    }
+
+    let operator = match tokens.peek() {
+        Some(Token::Equals) => None,
+        Some(token) if BinaryOp::from_assignment_token(token).is_some() => {
+            BinaryOp::from_assignment_token(token)
+        },
+        _ => return Err(ParseError::syntax(tokens, "expected assignment operator")),
+    };
+    tokens.next();
+
+    let (mut tokens, expression) = self::expression(tokens)?;
+    tokens = expect_sequence!(tokens, Token::Semicolon)?;
+
+    let assignment = Assignment {
+        span: span_from(target.span.start, &tokens),
+        target,
+        operator,
+        expression,
+    };
+
+    Ok((tokens, assignment))
+}
+
+pub fn expression(tokens: TokenStream) -> Result<Expr> {
+    expression_bp(tokens, 0)
+}
+
+/// Precedence climbing over binary operators, only consuming operators
+/// whose left binding power is at least `min_bp`.
+fn expression_bp(tokens: TokenStream, min_bp: u8) -> Result<Expr> {
+    let (mut tokens, mut lhs) = unary(tokens)?;
+
+    let mut prev_comparison = false;
+    while let Some(op) = tokens.peek().and_then(BinaryOp::from_token) {
+        let (l_bp, r_bp) = op.binding_power();
+        if l_bp < min_bp {
+            break;
+        }
+        if op.is_comparison() && prev_comparison {
+            return Err(ParseError::syntax(tokens, "comparison operators cannot be chained"));
+        }
+        prev_comparison = op.is_comparison();
+
+        tokens.next();
+        let (rest, rhs) = expression_bp(tokens, r_bp)?;
+        tokens = rest;
+        let span = lhs.span.to(rhs.span);
+        lhs = Expr::new(ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span);
+    }
+
+    Ok((tokens, lhs))
+}
+
+fn unary(mut tokens: TokenStream) -> Result<Expr> {
+    let start = tokens.position();
+    match tokens.peek().and_then(UnaryOp::from_token) {
+        Some(op) => {
+            tokens.next();
+            let (tokens, operand) = expression_bp(tokens, PREFIX_BINDING_POWER)?;
+            let span = span_from(start, &tokens);
+            Ok((tokens, Expr::new(ExprKind::Unary(op, Box::new(operand)), span)))
         },
+        None => postfix(tokens),
     }
+}
 
-    // TODO: blocks, let (tokens, body) = block(tokens)?;
-    tokens = expect_sequence!(tokens, Token::LeftBrace, Token::RightBrace)?;
+fn postfix(tokens: TokenStream) -> Result<Expr> {
+    let (mut tokens, mut expr) = primary(tokens)?;
+    let start = expr.span.start;
 
-    let function = Function {
-        name,
-        body: Block { statements: Vec::new() },
-        return_type,
+    loop {
+        let kind = match tokens.peek() {
+            Some(Token::LeftParen) => {
+                tokens.next();
+                let (rest, arguments) = call_arguments(tokens)?;
+                tokens = rest;
+                ExprKind::Call(Box::new(expr), arguments)
+            },
+            Some(Token::Period) => {
+                tokens.next();
+                let field = expect_identifier!(tokens);
+                ExprKind::Field(Box::new(expr), field)
+            },
+            Some(Token::LeftBracket) => {
+                tokens.next();
+                let (rest, index) = expression(tokens)?;
+                tokens = expect_sequence!(rest, Token::RightBracket)?;
+                ExprKind::Index(Box::new(expr), Box::new(index))
+            },
+            _ => break,
+        };
+        expr = Expr::new(kind, span_from(start, &tokens));
+    }
+
+    Ok((tokens, expr))
+}
+
+/// Parse comma separated arguments, after the opening parenthesis.
+fn call_arguments(mut tokens: TokenStream) -> Result<Vec<Expr>> {
+    let mut arguments = Vec::new();
+    loop {
+        if let Some(Token::RightParen) = tokens.peek() {
+            tokens.next();
+            break;
+        }
+
+        let (rest, argument) = expression(tokens)?;
+        tokens = rest;
+        arguments.push(argument);
+
+        match tokens.peek() {
+            Some(Token::Comma) => {
+                tokens.next();
+            },
+            Some(Token::RightParen) => {
+                tokens.next();
+                break;
+            },
+            _ => return Err(ParseError::syntax(tokens, "expected `,` or `)`")),
+        }
+    }
+    Ok((tokens, arguments))
+}
+
+/// Whether the stream is at the start of a struct literal, i.e. `Name { field:`
+///
+/// Requiring the first field means a block following an expression (such as
+/// the body of an `if`) is never mistaken for a struct literal.
+fn at_struct_literal(tokens: &TokenStream) -> bool {
+    let mut lookahead = tokens.clone();
+    matches!(lookahead.next(), Some(Token::Text))
+        && matches!(lookahead.next(), Some(Token::LeftBrace))
+        && matches!(lookahead.next(), Some(Token::Text))
+        && matches!(lookahead.next(), Some(Token::Colon))
+}
+
+fn struct_literal(mut tokens: TokenStream) -> Result<Expr> {
+    let start = tokens.position();
+    let name = expect_identifier!(tokens);
+    tokens = expect_sequence!(tokens, Token::LeftBrace)?;
+
+    let mut fields = Vec::new();
+    loop {
+        if let Some(Token::RightBrace) = tokens.peek() {
+            tokens.next();
+            break;
+        }
+
+        let field = expect_identifier!(tokens);
+        tokens = expect_sequence!(tokens, Token::Colon)?;
+        let (rest, value) = expression(tokens)?;
+        tokens = rest;
+        fields.push((field, value));
+
+        match tokens.peek() {
+            Some(Token::Comma) => {
+                tokens.next();
+            },
+            Some(Token::RightBrace) => {
+                tokens.next();
+                break;
+            },
+            _ => return Err(ParseError::syntax(tokens, "expected `,` or `}`")),
+        }
+    }
+
+    let span = span_from(start, &tokens);
+    Ok((tokens, Expr::new(ExprKind::Struct(name, fields), span)))
+}
+
+pub fn literal(mut tokens: TokenStream) -> Result<Literal> {
+    let token = match tokens.peek() {
+        Some(token) => token.clone(),
+        None => return Err(ParseError::syntax(tokens, "expected literal")),
+    };
+    let text = tokens.slice().unwrap();
+    let span = tokens.span().unwrap().into();
+    // errors point at the literal, so only consume it once it is valid
+    let error = |message: &str| Err(ParseError::syntax(tokens.clone(), message));
+
+    let value = match token {
+        Token::True => LiteralValue::Bool(true),
+        Token::False => LiteralValue::Bool(false),
+        Token::Integer => {
+            let (number, suffix) = NumberSuffix::split(text, &NumberSuffix::INTEGER);
+            let (digits, radix) = if let Some(digits) = number.strip_prefix("0x") {
+                (digits, 16)
+            } else if let Some(digits) = number.strip_prefix("0b") {
+                (digits, 2)
+            } else {
+                (number, 10)
+            };
+            let digits = digits.replace('_', "");
+            if digits.is_empty() {
+                return error("integer literal has no digits");
+            }
+            match u64::from_str_radix(&digits, radix) {
+                Ok(value) => LiteralValue::Integer(value, suffix),
+                Err(_) => return error("integer literal is too large"),
+            }
+        },
+        Token::Float => {
+            let (number, suffix) = NumberSuffix::split(text, &NumberSuffix::FLOAT);
+            match number.replace('_', "").parse() {
+                Ok(value) => LiteralValue::Float(value, suffix),
+                Err(_) => return error("invalid float literal"),
+            }
+        },
+        Token::String => {
+            let contents = &text[1..text.len() - 1];
+            let mut value = String::with_capacity(contents.len());
+            let mut chars = contents.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    value.push(c);
+                    continue;
+                }
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    _ => return error("unknown character escape"),
+                };
+                value.push(escaped);
+            }
+            LiteralValue::String(value)
+        },
+        _ => return error("expected literal"),
+    };
+    tokens.next();
+
+    let literal = Literal {
+        value,
+        text: text.to_owned(),
+        span,
     };
 
-    Ok((tokens, Item::Function(function)))
+    Ok((tokens, literal))
+}
+
+fn primary(mut tokens: TokenStream) -> Result<Expr> {
+    let start = tokens.position();
+    match tokens.peek() {
+        Some(Token::Integer | Token::Float | Token::True | Token::False | Token::String) => {
+            let (tokens, literal) = literal(tokens)?;
+            let span = literal.span;
+            Ok((tokens, Expr::new(ExprKind::Literal(literal), span)))
+        },
+        Some(Token::Text) if at_struct_literal(&tokens) => struct_literal(tokens),
+        Some(Token::Text) => {
+            let identifier = expect_identifier!(tokens);
+            let span = identifier.span;
+            Ok((tokens, Expr::new(ExprKind::Identifier(identifier), span)))
+        },
+        Some(Token::LeftParen) => {
+            tokens.next();
+            let (tokens, inner) = expression(tokens)?;
+            let tokens = expect_sequence!(tokens, Token::RightParen)?;
+            let span = span_from(start, &tokens);
+            Ok((tokens, Expr::new(ExprKind::Group(Box::new(inner)), span)))
+        },
+        Some(Token::If) => {
+            let (tokens, if_expr) = if_expression(tokens)?;
+            let span = if_expr.span;
+            Ok((tokens, Expr::new(ExprKind::If(Box::new(if_expr)), span)))
+        },
+        _ => Err(ParseError::syntax(tokens, "expected expression")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::span::{SourcePos, SpanMessage};
+
+    fn kinds(statements: &[Statement]) -> Vec<&StatementKind> {
+        statements.iter().map(Statement::kind).collect()
+    }
+
+    fn parse_function(source: &str) -> Function {
+        let module = parse_module(source).unwrap();
+        match module.items.into_iter().next().map(|item| item.kind) {
+            Some(ItemKind::Function(function)) => function,
+            other => panic!("expected function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_empty_body() {
+        let function = parse_function("fn main() {}");
+        assert!(function.body().statements().is_empty());
+        assert!(function.body().tail().is_none());
+    }
+
+    #[test]
+    fn parses_statements() {
+        let function = parse_function("
+            fn main() -> Color {
+                let a = b;
+                let mut c: Color = a;
+                c = d;
+                {
+                    let e;
+                    e;
+                }
+                c
+            }
+        ");
+        let body = function.body();
+        let statements = body.statements();
+        assert_eq!(statements.len(), 4);
+
+        match statements[0].kind() {
+            StatementKind::Let(l) => {
+                assert_eq!(l.binding().str(), "a");
+                assert!(!l.mutable());
+                assert!(l.type_name().is_none());
+            },
+            other => panic!("expected let, got {:?}", other),
+        }
+        match statements[1].kind() {
+            StatementKind::Let(l) => {
+                assert_eq!(l.binding().str(), "c");
+                assert!(l.mutable());
+                assert!(l.type_name().is_some());
+            },
+            other => panic!("expected let mut, got {:?}", other),
+        }
+        match statements[2].kind() {
+            StatementKind::Assignment(a) => {
+                assert!(matches!(a.target().kind(), ExprKind::Identifier(i) if i.str() == "c"));
+                assert!(a.operator().is_none());
+            },
+            other => panic!("expected assignment, got {:?}", other),
+        }
+        match statements[3].kind() {
+            StatementKind::Block(b) => {
+                assert_eq!(b.statements().len(), 2);
+                assert!(matches!(b.statements()[1].kind(), StatementKind::Expr(_)));
+            },
+            other => panic!("expected block, got {:?}", other),
+        }
+        assert!(matches!(body.tail().map(Expr::kind), Some(ExprKind::Identifier(i)) if i.str() == "c"));
+    }
+
+    #[test]
+    fn rejects_missing_semicolon() {
+        assert!(parse_module("fn main() { let a = b }").is_err());
+        assert!(parse_module("fn main() { a b }").is_err());
+        assert!(parse_module("fn main() { a = b }").is_err());
+    }
+
+    #[test]
+    fn rejects_unclosed_block() {
+        assert!(parse_module("fn main() { let a = b;").is_err());
+    }
+
+    #[test]
+    fn parses_function_arguments() {
+        let function = parse_function("
+            fn shade(scene: Scene, material: Material,) -> vec4 {
+                scene
+            }
+        ");
+        let names: Vec<_> = function.arguments().iter()
+            .map(|(name, _)| name.str())
+            .collect();
+        assert_eq!(names, ["scene", "material"]);
+
+        assert!(parse_function("fn main() {}").arguments().is_empty());
+        assert!(parse_module("fn main(a) {}").is_err());
+        assert!(parse_module("fn main(a: A b: B) {}").is_err());
+    }
+
+    #[test]
+    fn parses_declare_function() {
+        let module = parse_module("
+            declare fn shade(scene: Scene, material: Material, object: Object) -> vec4;
+            declare fn discard();
+        ").unwrap();
+        let declared: Vec<_> = module.items().iter()
+            .map(|item| match item.kind() {
+                ItemKind::Declare(Declare::Function(f)) => f,
+                other => panic!("expected declare fn, got {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(declared[0].name().str(), "shade");
+        assert_eq!(declared[0].arguments().len(), 3);
+        assert!(matches!(declared[0].return_type().kind(), TypeNameKind::Identifier(i) if i.str() == "vec4"));
+
+        assert_eq!(declared[1].name().str(), "discard");
+        assert!(matches!(declared[1].return_type().kind(), TypeNameKind::Tuple(t) if t.is_empty()));
+
+        assert!(parse_module("declare fn shade(scene: Scene) -> vec4 {}").is_err());
+    }
+
+    /// Render an expression fully parenthesized, to check precedence.
+    fn sexpr(expr: &Expr) -> String {
+        match expr.kind() {
+            ExprKind::Literal(literal) => literal.text().to_owned(),
+            ExprKind::Identifier(i) => i.str().to_owned(),
+            ExprKind::Unary(op, operand) => format!("({}{})", op.symbol(), sexpr(operand)),
+            ExprKind::Binary(op, lhs, rhs) => format!("({} {} {})", sexpr(lhs), op.symbol(), sexpr(rhs)),
+            ExprKind::Group(inner) => sexpr(inner),
+            ExprKind::Call(callee, args) => {
+                let args: Vec<_> = args.iter().map(sexpr).collect();
+                format!("{}({})", sexpr(callee), args.join(", "))
+            },
+            ExprKind::Field(base, field) => format!("{}.{}", sexpr(base), field.str()),
+            ExprKind::Index(base, index) => format!("{}[{}]", sexpr(base), sexpr(index)),
+            ExprKind::Struct(name, fields) => {
+                let fields: Vec<_> = fields.iter()
+                    .map(|(field, value)| format!("{}: {}", field.str(), sexpr(value)))
+                    .collect();
+                format!("{} {{ {} }}", name.str(), fields.join(", "))
+            },
+            ExprKind::If(if_expr) => {
+                let mut rendered = format!("if {} {{..}}", sexpr(if_expr.condition()));
+                let mut otherwise = if_expr.otherwise();
+                while let Some(branch) = otherwise {
+                    match branch {
+                        Else::If(inner) => {
+                            rendered.push_str(&format!(" else if {} {{..}}", sexpr(inner.condition())));
+                            otherwise = inner.otherwise();
+                        },
+                        Else::Block(_) => {
+                            rendered.push_str(" else {..}");
+                            otherwise = None;
+                        },
+                    }
+                }
+                rendered
+            },
+        }
+    }
+
+    fn parse_expr(source: &str) -> std::result::Result<String, String> {
+        let buffer = TokenStream::buffer(source);
+        let tokens = TokenStream::new(&buffer, source);
+        match expression(tokens) {
+            Ok((tokens, expr)) if tokens.eof() => Ok(sexpr(&expr)),
+            Ok((tokens, _)) => Err(format!("trailing tokens at {:?}", tokens.slice())),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_precedence() {
+        assert_eq!(parse_expr("a + b * c").unwrap(), "(a + (b * c))");
+        assert_eq!(parse_expr("a * b + c").unwrap(), "((a * b) + c)");
+        assert_eq!(parse_expr("a - b - c").unwrap(), "((a - b) - c)");
+        assert_eq!(parse_expr("(a - b) * c").unwrap(), "((a - b) * c)");
+        assert_eq!(parse_expr("a || b && c == d").unwrap(), "(a || (b && (c == d)))");
+        assert_eq!(parse_expr("a & b | c ^ d").unwrap(), "((a & b) | (c ^ d))");
+        assert_eq!(parse_expr("a << b + c < d").unwrap(), "((a << (b + c)) < d)");
+    }
+
+    #[test]
+    fn parses_unary() {
+        assert_eq!(parse_expr("-a * b").unwrap(), "((-a) * b)");
+        assert_eq!(parse_expr("!a && !b").unwrap(), "((!a) && (!b))");
+        assert_eq!(parse_expr("--a").unwrap(), "(-(-a))");
+        assert_eq!(parse_expr("-a.x").unwrap(), "(-a.x)");
+    }
+
+    #[test]
+    fn parses_postfix() {
+        assert_eq!(parse_expr("f()").unwrap(), "f()");
+        assert_eq!(parse_expr("mix(a, b * c, t,)").unwrap(), "mix(a, (b * c), t)");
+        assert_eq!(parse_expr("v.xyz.x").unwrap(), "v.xyz.x");
+        assert_eq!(parse_expr("lights[i].color").unwrap(), "lights[i].color");
+        assert_eq!(parse_expr("m[i][j] * v").unwrap(), "(m[i][j] * v)");
+    }
+
+    #[test]
+    fn rejects_chained_comparisons() {
+        assert!(parse_expr("a < b < c").is_err());
+        assert!(parse_expr("a == b != c").is_err());
+        assert_eq!(parse_expr("a < b && b < c").unwrap(), "((a < b) && (b < c))");
+    }
+
+    #[test]
+    fn parses_struct_item() {
+        let module = parse_module("
+            struct Material {
+                albedo: vec3,
+                roughness: f32,
+            }
+            struct Light { color: vec3 }
+        ").unwrap();
+        match module.items()[0].kind() {
+            ItemKind::Struct(st) => {
+                assert_eq!(st.name().str(), "Material");
+                let fields: Vec<_> = st.fields().iter().map(|(f, _)| f.str()).collect();
+                assert_eq!(fields, ["albedo", "roughness"]);
+            },
+            other => panic!("expected struct, got {:?}", other),
+        }
+        assert!(matches!(module.items()[1].kind(), ItemKind::Struct(st) if st.fields().len() == 1));
+
+        assert!(parse_module("struct Material { albedo }").is_err());
+    }
+
+    #[test]
+    fn reports_empty_structs_at_their_braces() {
+        // the struct is last, and then followed by another item
+        for (text, braces) in [("struct Empty {}", 13..15), ("struct Empty { }\nfn f() {}", 13..16)] {
+            let parsed = ParsedModule::new(crate::cst::parse(text));
+            let errors = parsed.errors();
+            assert_eq!(errors.len(), 1, "{}", text);
+            assert_eq!(errors[0].message(), "structs must have at least one field");
+            assert_eq!(errors[0].span(), ByteSpan::from(braces));
+        }
+    }
+
+    #[test]
+    fn parses_struct_literal() {
+        assert_eq!(
+            parse_expr("Material { albedo: a * b, roughness: r }.albedo").unwrap(),
+            "Material { albedo: (a * b), roughness: r }.albedo",
+        );
+        assert_eq!(
+            parse_expr("f(Light { color: c, })").unwrap(),
+            "f(Light { color: c })",
+        );
+        assert!(parse_expr("Material { albedo: a roughness: r }").is_err());
+    }
+
+    #[test]
+    fn parses_if_expression() {
+        assert_eq!(parse_expr("if a { b } else { c }").unwrap(), "if a {..} else {..}");
+        assert_eq!(
+            parse_expr("if a < b { b } else if c { c } else { d }").unwrap(),
+            "if (a < b) {..} else if c {..} else {..}",
+        );
+        assert!(parse_expr("if a { b } else c").is_err());
+    }
+
+    #[test]
+    fn parses_control_flow() {
+        let function = parse_function("
+            fn main() -> f32 {
+                let x = if a { b } else { c };
+                if a {
+                    return;
+                } else if b {
+                    x = c;
+                }
+                for i in a..b {
+                    if i == c { continue; }
+                    break;
+                }
+                for j in a..=lights.len() {}
+                while x < y { x += y; }
+                return x;
+            }
+        ");
+        let statements = function.body().statements();
+        assert_eq!(statements.len(), 6);
+        assert!(matches!(statements[0].kind(), StatementKind::Let(l) if matches!(l.expression().map(Expr::kind), Some(ExprKind::If(_)))));
+        match statements[1].kind() {
+            StatementKind::Expr(expr) => {
+                let if_expr = match expr.kind() {
+                    ExprKind::If(if_expr) => if_expr,
+                    other => panic!("expected if, got {:?}", other),
+                };
+                assert!(matches!(kinds(if_expr.then().statements())[..], [StatementKind::Return(None)]));
+                assert!(matches!(if_expr.otherwise(), Some(Else::If(inner)) if inner.otherwise().is_none()));
+            },
+            other => panic!("expected if, got {:?}", other),
+        }
+        match statements[2].kind() {
+            StatementKind::For(f) => {
+                assert_eq!(f.binding().str(), "i");
+                assert!(!f.range().inclusive());
+                assert!(matches!(kinds(f.body().statements())[..], [StatementKind::Expr(_), StatementKind::Break]));
+            },
+            other => panic!("expected for, got {:?}", other),
+        }
+        assert!(matches!(statements[3].kind(), StatementKind::For(f) if f.range().inclusive()));
+        assert!(matches!(statements[4].kind(), StatementKind::While(_)));
+        assert!(matches!(statements[5].kind(), StatementKind::Return(Some(_))));
+    }
+
+    #[test]
+    fn parses_if_as_tail() {
+        let function = parse_function("fn main() -> f32 { if a { b } else { c } }");
+        assert!(function.body().statements().is_empty());
+        assert!(matches!(function.body().tail().map(Expr::kind), Some(ExprKind::If(_))));
+    }
+
+    #[test]
+    fn rejects_malformed_control_flow() {
+        assert!(parse_module("fn main() { for i in a { } }").is_err());
+        assert!(parse_module("fn main() { for in a..b { } }").is_err());
+        assert!(parse_module("fn main() { while a; }").is_err());
+        assert!(parse_module("fn main() { break }").is_err());
+        assert!(parse_module("fn main() { return a }").is_err());
+    }
+
+    fn parse_literal(source: &str) -> std::result::Result<LiteralValue, String> {
+        let buffer = TokenStream::buffer(source);
+        match literal(TokenStream::new(&buffer, source)) {
+            Ok((_, literal)) => Ok(literal.value().clone()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_literals() {
+        use NumberSuffix::*;
+        assert_eq!(parse_literal("42").unwrap(), LiteralValue::Integer(42, None));
+        assert_eq!(parse_literal("3u").unwrap(), LiteralValue::Integer(3, Some(U32)));
+        assert_eq!(parse_literal("1_000i32").unwrap(), LiteralValue::Integer(1000, Some(I32)));
+        assert_eq!(parse_literal("0xFFu").unwrap(), LiteralValue::Integer(255, Some(U32)));
+        assert_eq!(parse_literal("0b1010").unwrap(), LiteralValue::Integer(10, None));
+        assert_eq!(parse_literal("0.5").unwrap(), LiteralValue::Float(0.5, None));
+        assert_eq!(parse_literal("1.0f32").unwrap(), LiteralValue::Float(1.0, Some(F32)));
+        assert_eq!(parse_literal("2.5e-1lf").unwrap(), LiteralValue::Float(0.25, Some(F64)));
+        assert_eq!(parse_literal("1e3").unwrap(), LiteralValue::Float(1000.0, None));
+        assert_eq!(parse_literal("3f").unwrap(), LiteralValue::Float(3.0, Some(F32)));
+        assert_eq!(parse_literal("true").unwrap(), LiteralValue::Bool(true));
+        assert_eq!(parse_literal("false").unwrap(), LiteralValue::Bool(false));
+        assert_eq!(
+            parse_literal(r#""say \"hi\"\n""#).unwrap(),
+            LiteralValue::String("say \"hi\"\n".to_owned()),
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_literals() {
+        assert!(parse_literal("0b_").is_err());
+        assert!(parse_literal("0xFFFFFFFFFFFFFFFFF").is_err());
+        assert!(parse_literal(r#""\q""#).is_err());
+    }
+
+    #[test]
+    fn keeps_literal_spans() {
+        const SOURCE: &str = "a + 0.5f32";
+        let buffer = TokenStream::buffer(SOURCE);
+        let (_, expr) = expression(TokenStream::new(&buffer, SOURCE)).unwrap();
+        match expr.kind() {
+            ExprKind::Binary(_, _, rhs) => match rhs.kind() {
+                ExprKind::Literal(literal) => {
+                    assert_eq!(literal.span(), ByteSpan::new(4, 10));
+                    assert_eq!(literal.text(), "0.5f32");
+                },
+                other => panic!("expected literal, got {:?}", other),
+            },
+            other => panic!("expected binary, got {:?}", other),
+        }
+        assert_eq!(parse_expr("x * 2.0 + 1").unwrap(), "((x * 2.0) + 1)");
+        assert_eq!(parse_expr("v[0].x").unwrap(), "v[0].x");
+        assert!(parse_module("fn main() { for i in 0..4 {} }").is_ok());
+    }
+
+    #[test]
+    fn rejects_reserved_identifiers() {
+        assert!(parse_module("fn main() { let base_color = _unused; }").is_ok());
+        assert!(parse_module("fn main() { let größe = a; }").is_ok());
+        assert!(parse_module("fn main() { let match = a; }").is_err());
+        assert!(parse_module("fn main() { let gl_Position = a; }").is_err());
+        assert!(parse_module("fn main() { let a__b = a; }").is_err());
+        assert!(parse_module("fn main() { let xv_a = a; }").is_err());
+        assert!(parse_module("struct gl_PerVertex { a: f32 }").is_err());
+    }
+
+    #[test]
+    fn spans_nodes() {
+        const SOURCE: &str = "uniform a: A;\nfn main() {\n    let x = f(a.b) * 2;\n}\n";
+        let module = parse_module(SOURCE).unwrap();
+        let text = |span: ByteSpan| &SOURCE[span.range()];
+
+        assert_eq!(text(module.items()[0].span()), "uniform a: A;");
+        let function = match module.items()[1].kind() {
+            ItemKind::Function(function) => function,
+            other => panic!("expected function, got {:?}", other),
+        };
+        assert_eq!(text(function.name().span()), "main");
+        assert_eq!(text(function.arguments().span()), "()");
+        assert_eq!(text(function.body().span()), "{\n    let x = f(a.b) * 2;\n}");
+
+        let statement = &function.body().statements()[0];
+        assert_eq!(text(statement.span()), "let x = f(a.b) * 2;");
+        let expr = match statement.kind() {
+            StatementKind::Let(l) => l.expression().unwrap(),
+            other => panic!("expected let, got {:?}", other),
+        };
+        assert_eq!(text(expr.span()), "f(a.b) * 2");
+        match expr.kind() {
+            ExprKind::Binary(_, lhs, rhs) => {
+                assert_eq!(text(lhs.span()), "f(a.b)");
+                assert_eq!(text(rhs.span()), "2");
+                match lhs.kind() {
+                    ExprKind::Call(_, args) => assert_eq!(text(args[0].span()), "a.b"),
+                    other => panic!("expected call, got {:?}", other),
+                }
+            },
+            other => panic!("expected binary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn locates_parse_errors() {
+        let source = Arc::new(SourceFile::new("test.xs".into(), "fn main() {\n    let = a;\n}\n".into()));
+        match parse_file(&source) {
+            Err(CompilerError::Shader(err)) => {
+                let annotations = err.annotations();
+                assert_eq!(annotations.sources[0].name(), "test.xs");
+                assert_eq!(annotations.primary.start, SourcePos::new(1, 8));
+                assert_eq!(annotations.primary.end, SourcePos::new(1, 9));
+            },
+            other => panic!("expected shader error, got {:?}", other),
+        }
+
+        // errors at end of file point just past the last character
+        let source = Arc::new(SourceFile::new("test.xs".into(), "fn main() {".into()));
+        match parse_file(&source) {
+            Err(CompilerError::Shader(err)) => {
+                assert_eq!(err.annotations().primary.start, SourcePos::new(0, 11));
+            },
+            other => panic!("expected shader error, got {:?}", other),
+        }
+    }
+
+    fn parse_type(source: &str) -> TypeName {
+        let buffer = TokenStream::buffer(source);
+        let (tokens, type_name) = type_name(TokenStream::new(&buffer, source)).unwrap();
+        assert!(tokens.eof(), "unparsed input after {}", source);
+        type_name
+    }
+
+    /// Write a type back out in a canonical form.
+    fn show_type(type_name: &TypeName) -> String {
+        let list = |types: &[TypeName]| types.iter().map(show_type).collect::<Vec<_>>().join(", ");
+        match type_name.kind() {
+            TypeNameKind::Identifier(name) => name.str().to_owned(),
+            TypeNameKind::Tuple(elements) if elements.len() == 1 => format!("({},)", list(elements)),
+            TypeNameKind::Tuple(elements) => format!("({})", list(elements)),
+            TypeNameKind::Literal(literal) => literal.text().to_owned(),
+            TypeNameKind::Array(element, Some(length)) => format!("[{}; {}]", show_type(element), sexpr(length)),
+            TypeNameKind::Array(element, None) => format!("[{}]", show_type(element)),
+            TypeNameKind::Generic(name, arguments) => format!("{}<{}>", name.str(), list(arguments)),
+        }
+    }
+
+    #[test]
+    fn parses_const_globals() {
+        let module = parse_module("
+            const MAX_LIGHTS: u32 = 8;
+            pub const SCALE: f32 = 1.0 / (2.0 * PI);
+            declare const samples: u32;
+            uniform lights: [Light; MAX_LIGHTS];
+        ").unwrap();
+        let globals: Vec<&Global> = module.items().iter()
+            .filter_map(|item| match item.kind() {
+                ItemKind::Global(global) => Some(global),
+                _ => None,
+            })
+            .collect();
+
+        assert!(matches!(globals[0].qualifier(), GlobalQualifier::Const));
+        assert_eq!(globals[0].identifier().str(), "MAX_LIGHTS");
+        assert_eq!(globals[0].initializer().map(sexpr).as_deref(), Some("8"));
+        assert_eq!(globals[1].initializer().map(sexpr).as_deref(), Some("(1.0 / (2.0 * PI))"));
+        assert!(globals[2].initializer().is_none());
+        assert!(matches!(module.items()[2].kind(), ItemKind::Declare(Declare::Const(_))));
+
+        assert!(parse_module("const A: u32;").is_err());
+        assert!(parse_module("const A = 8;").is_err());
+        assert!(parse_module("const A: u32 = ;").is_err());
+        assert!(parse_module("uniform a: A = 1;").is_err());
+    }
+
+    #[test]
+    fn parses_compound_types() {
+        let cases = [
+            ("f32", "f32"),
+            ("[f32; 4]", "[f32; 4]"),
+            ("[vec4; LIGHTS * 2]", "[vec4; (LIGHTS * 2)]"),
+            ("[Light]", "[Light]"),
+            ("()", "()"),
+            ("(f32)", "f32"),
+            ("(f32,)", "(f32,)"),
+            ("(vec3, [f32; 2])", "(vec3, [f32; 2])"),
+            ("vec<f32, 3>", "vec<f32, 3>"),
+            ("array<vec<f32, 3>>", "array<vec<f32, 3>>"),
+            ("a<b<c<d>>>", "a<b<c<d>>>"),
+            ("a<b<c>, d>", "a<b<c>, d>"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(show_type(&parse_type(source)), expected);
+        }
+
+        // a split `>>` still gives each type its own span
+        const NESTED: &str = "array<vec<f32, 3>>";
+        let outer = parse_type(NESTED);
+        match outer.kind() {
+            TypeNameKind::Generic(_, arguments) => {
+                assert_eq!(&NESTED[arguments[0].span().range()], "vec<f32, 3>");
+            },
+            other => panic!("expected generic, got {:?}", other),
+        }
+        assert_eq!(&NESTED[outer.span().range()], NESTED);
+
+        assert!(parse_module("uniform a: [f32; 4;").is_err());
+        assert!(parse_module("uniform a: vec<f32, 3;").is_err());
+        assert!(parse_module("uniform a: vec<f32, 3>>;").is_err());
+        assert!(parse_module("uniform a: 3;").is_err());
+        assert!(parse_module("uniform a: (f32 f32);").is_err());
+        assert!(parse_module("
+            uniform lights: [Light; 4];
+            uniform albedo: sampler2D;
+            in normal: vec<f32, 3>;
+            fn f(pair: (f32, f32)) -> [vec4] {}
+        ").is_ok());
+    }
+
+    #[test]
+    fn recognizes_builtin_types() {
+        let builtin = |source: &str| parse_type(source).builtin();
+        assert_eq!(builtin("f32"), Some(Builtin::Scalar(Scalar::F32)));
+        assert_eq!(builtin("vec3"), Some(Builtin::Vector { scalar: Scalar::F32, size: 3 }));
+        assert_eq!(builtin("ivec2"), Some(Builtin::Vector { scalar: Scalar::I32, size: 2 }));
+        assert_eq!(builtin("vec<u32, 4>"), Some(Builtin::Vector { scalar: Scalar::U32, size: 4 }));
+        assert_eq!(builtin("mat4"), Some(Builtin::Matrix { scalar: Scalar::F32, columns: 4, rows: 4 }));
+        assert_eq!(builtin("dmat2x3"), Some(Builtin::Matrix { scalar: Scalar::F64, columns: 2, rows: 3 }));
+        assert_eq!(builtin("mat<f32, 3>"), Some(Builtin::Matrix { scalar: Scalar::F32, columns: 3, rows: 3 }));
+        assert_eq!(builtin("sampler2D"), Some(Builtin::Sampler { dimension: Dimension::D2, shadow: false }));
+        assert_eq!(builtin("samplerCubeShadow"), Some(Builtin::Sampler { dimension: Dimension::Cube, shadow: true }));
+        assert_eq!(builtin("texture2DArray"), Some(Builtin::Texture { dimension: Dimension::D2Array }));
+
+        for user_defined in ["Material", "vec5", "imat3", "vec<f32, 5>", "sampler3DShadow", "[f32; 4]", "myvec3"] {
+            assert_eq!(builtin(user_defined), None, "{}", user_defined);
+        }
+    }
+
+    #[test]
+    fn parses_use_trees() {
+        let module = parse_module("
+            use lighting::{pbr, shadow as sh, util::{a, b},};
+            pub use common::*;
+            use super::super::noise::perlin;
+            use crate::main;
+        ").unwrap();
+        let uses: Vec<(&Item, &Use)> = module.items().iter()
+            .map(|item| match item.kind() {
+                ItemKind::Use(u) => (item, u),
+                other => panic!("expected use, got {:?}", other),
+            })
+            .collect();
+        let describe = |u: &Use| -> Vec<String> {
+            u.imports().iter()
+                .map(|import| match import {
+                    Import::Single { path, name } => {
+                        let path: Vec<_> = path.iter().map(|i| i.str()).collect();
+                        format!("{} as {}", path.join("::"), name.str())
+                    },
+                    Import::Glob { path } => {
+                        let path: Vec<_> = path.iter().map(|i| i.str()).collect();
+                        format!("{}::*", path.join("::"))
+                    },
+                })
+                .collect()
+        };
+
+        assert_eq!(describe(uses[0].1), [
+            "lighting::pbr as pbr",
+            "lighting::shadow as sh",
+            "lighting::util::a as a",
+            "lighting::util::b as b",
+        ]);
+        assert_eq!(uses[0].0.visibility(), Visibility::Private);
+        assert_eq!(describe(uses[1].1), ["common::*"]);
+        assert_eq!(uses[1].0.visibility(), Visibility::Public);
+        assert_eq!(uses[2].1.root(), PathRoot::Super(2));
+        assert_eq!(describe(uses[2].1), ["noise::perlin as perlin"]);
+        assert_eq!(uses[3].1.root(), PathRoot::Crate);
+
+        assert!(parse_module("use a::;").is_err());
+        assert!(parse_module("use a::{b c};").is_err());
+        assert!(parse_module("use a as;").is_err());
+        assert!(parse_module("use a::super::b;").is_err());
+    }
+
+    #[test]
+    fn parses_visibility() {
+        let module = parse_module("
+            pub fn shade() {}
+            fn helper() {}
+            pub struct Material { albedo: vec3 }
+            pub uniform scene: Scene;
+            #[location(0)] pub out color: vec4;
+            pub declare const samples: u32;
+        ").unwrap();
+        let visibility: Vec<_> = module.items().iter().map(Item::visibility).collect();
+        assert_eq!(visibility, [
+            Visibility::Public,
+            Visibility::Private,
+            Visibility::Public,
+            Visibility::Public,
+            Visibility::Public,
+            Visibility::Public,
+        ]);
+
+        assert!(parse_module("pub pub fn main() {}").is_err());
+        assert!(parse_module("pub #[inline] fn main() {}").is_err());
+    }
+
+    #[test]
+    fn attaches_docs_and_attributes() {
+        const SOURCE: &str = "
+            // not documentation
+            /// Surface color.
+            ///
+            /// Linear, not sRGB.
+            #[location(0)]
+            #[interpolate(flat)]
+            out color: vec4;
+
+            /**
+             * Entry point.
+             */
+            #[inline] fn main() {}
+
+            //// four slashes is a plain comment
+            /***/
+            uniform a: A;
+        ";
+        let module = parse_module(SOURCE).unwrap();
+        let items = module.items();
+
+        assert_eq!(items[0].doc_text().as_deref(), Some("Surface color.\n\nLinear, not sRGB."));
+        assert_eq!(&SOURCE[items[0].span().range()], "#[location(0)]\n            #[interpolate(flat)]\n            out color: vec4;");
+        let location = items[0].attribute("location").unwrap();
+        assert_eq!(sexpr(&location.arguments().unwrap()[0]), "0");
+        let interpolate = items[0].attribute("interpolate").unwrap();
+        assert_eq!(sexpr(&interpolate.arguments().unwrap()[0]), "flat");
+
+        assert_eq!(items[1].doc_text().as_deref(), Some("Entry point."));
+        let inline = items[1].attribute("inline").unwrap();
+        assert!(inline.arguments().is_none());
+        assert!(matches!(items[1].kind(), ItemKind::Function(_)));
+
+        assert!(items[2].docs().is_empty());
+        assert!(items[2].attributes().is_empty());
+
+        assert!(parse_module("#[location(0) out color: vec4;").is_err());
+        assert!(parse_module("#[] out color: vec4;").is_err());
+        assert!(parse_module("#[inline]").is_err());
+    }
+
+    #[test]
+    fn recovers_at_item_boundaries() {
+        let source = "use a::b\nfn main() { let = a; }\nuniform c: C;\nfn f() { a b }\nstruct S { x: X }";
+        let (module, errors) = module(&cst::parse(source));
+        let text = |span: ByteSpan| &source[span.range()];
+
+        assert_eq!(errors.len(), 3);
+        let items = module.items();
+        assert_eq!(items.len(), 5);
+        assert!(matches!(items[0].kind(), ItemKind::Error));
+        assert_eq!(text(items[0].span()), "use a::b");
+        assert!(matches!(items[1].kind(), ItemKind::Error));
+        assert_eq!(text(items[1].span()), "fn main() { let = a; }");
+        assert!(matches!(items[2].kind(), ItemKind::Global(_)));
+        assert!(matches!(items[3].kind(), ItemKind::Error));
+        assert_eq!(text(items[3].span()), "fn f() { a b }");
+        assert!(matches!(items[4].kind(), ItemKind::Struct(_)));
+    }
+
+    #[test]
+    fn recovers_from_unbalanced_braces() {
+        let source = "fn main() { let x = 1;\ndeclare fn f() -> vec4;\n} let y; fn g() {}";
+        let (module, errors) = module(&cst::parse(source));
+
+        assert_eq!(errors.len(), 3);
+        let items = module.items();
+        assert!(matches!(items[0].kind(), ItemKind::Error));
+        assert!(matches!(items[1].kind(), ItemKind::Declare(_)));
+        assert!(matches!(items[2].kind(), ItemKind::Error));
+        assert!(matches!(items[3].kind(), ItemKind::Error));
+        assert!(matches!(items[4].kind(), ItemKind::Function(f) if f.name().str() == "g"));
+    }
+
+    #[test]
+    fn reports_every_parse_error() {
+        let source = Arc::new(SourceFile::new("test.xs".into(), "fn main() { let = a; }\nfn f() { a b }\n".into()));
+        let (module, errors) = parse_file_partial(&source);
+        assert_eq!(module.items().len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].annotations().primary.start, SourcePos::new(1, 11));
+
+        match parse_file(&source) {
+            Err(CompilerError::Shaders(errors)) => assert_eq!(errors.errors().len(), 2),
+            other => panic!("expected shader errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_unterminated_comments() {
+        let source = Arc::new(SourceFile::new("test.xs".into(), "fn main() {\n    /* let a = b; */ /* c\n}\n".into()));
+        match parse_file(&source) {
+            Err(CompilerError::Shader(err)) => {
+                let annotations = err.annotations();
+                assert_eq!(annotations.message().map(SpanMessage::text), Some("unterminated block comment"));
+                assert_eq!(annotations.primary.start, SourcePos::new(1, 21));
+                assert_eq!(annotations.primary.end, SourcePos::new(1, 23));
+            },
+            other => panic!("expected shader error, got {:?}", other),
+        }
+
+        assert!(parse_module("fn main() {} /*").is_err());
+        assert!(parse_module("fn main() {} /* */").is_ok());
+        // `//` inside a block comment does not hide its `*/`
+        assert!(parse_module("fn f() {} /* // */ fn g() {}").is_ok());
+        assert_eq!(parse_module("/* a // b */ fn x() {}").unwrap().items().len(), 1);
+    }
+
+    #[test]
+    fn reparses_edited_items() {
+        const SOURCE: &str = "uniform a: A;\nfn f() { a = b; }\nfn g() { c = d; }\n";
+        let mut parsed = ParsedModule::new(cst::parse(SOURCE));
+        let ids = parsed.module().ids().to_vec();
+        assert_eq!(ids.len(), 3);
+
+        let b = SOURCE.find('b').unwrap();
+        let changes = parsed.edit(&TextEdit::new(ByteSpan::new(b, b + 1), "b + e".into()));
+        assert_eq!(changes.removed(), &ids[1..2]);
+        assert_eq!(changes.added().len(), 1);
+        assert!(!ids.contains(&changes.added()[0]));
+
+        let module = parsed.module().clone();
+        assert_eq!(module.ids(), &[ids[0], changes.added()[0], ids[2]]);
+        assert!(parsed.errors().is_empty());
+
+        // items after the edit move, as if the whole source was parsed again
+        let text = parsed.tree().text();
+        let fresh = parse_module(&text).unwrap();
+        for (item, fresh) in module.items().iter().zip(fresh.items()) {
+            assert_eq!(item.span(), fresh.span());
+        }
+        let g = module.item(ids[2]).unwrap();
+        assert_eq!(&text[g.kind().name().unwrap().span().range()], "g");
+        match g.kind() {
+            ItemKind::Function(function) => {
+                assert_eq!(&text[function.body().statements()[0].span().range()], "c = d;");
+            },
+            other => panic!("expected function, got {:?}", other),
+        }
+
+        // breaking an item reports it, and fixing it clears the error
+        let semicolon = text.find("e;").unwrap() + 1;
+        parsed.edit(&TextEdit::delete(ByteSpan::new(semicolon, semicolon + 1)));
+        assert_eq!(parsed.errors().len(), 1);
+        assert!(matches!(parsed.module().items()[1].kind(), ItemKind::Error));
+        parsed.edit(&TextEdit::insert(semicolon, ";".into()));
+        assert!(parsed.errors().is_empty());
+        assert_eq!(parsed.module().ids()[2], ids[2]);
+
+        // unterminated comments swallow everything after them
+        parsed.edit(&TextEdit::insert(0, "/*".into()));
+        assert_eq!(parsed.errors().len(), 1);
+        assert!(parsed.module().items().is_empty());
+    }
+
+    #[test]
+    fn detects_swizzles() {
+        let swizzle = |source: &str| {
+            let buffer = TokenStream::buffer(source);
+            let (_, expr) = expression(TokenStream::new(&buffer, source)).unwrap();
+            expr.swizzle().map(|s| s.to_owned())
+        };
+        assert_eq!(swizzle("v.xyz").as_deref(), Some("xyz"));
+        assert_eq!(swizzle("c.rgba").as_deref(), Some("rgba"));
+        assert_eq!(swizzle("v.xg"), None);
+        assert_eq!(swizzle("m.albedo"), None);
+        assert_eq!(swizzle("v"), None);
+    }
+
+    #[test]
+    fn parses_assignment_targets() {
+        let function = parse_function("
+            fn main() {
+                v.x += a;
+                lights[i].color = c;
+                f(a);
+            }
+        ");
+        let statements = function.body().statements();
+        match statements[0].kind() {
+            StatementKind::Assignment(a) => assert_eq!(a.operator(), Some(BinaryOp::Add)),
+            other => panic!("expected assignment, got {:?}", other),
+        }
+        assert!(matches!(statements[1].kind(), StatementKind::Assignment(_)));
+        assert!(matches!(statements[2].kind(), StatementKind::Expr(e) if matches!(e.kind(), ExprKind::Call(..))));
+
+        assert!(parse_module("fn main() { f() = a; }").is_err());
+        assert!(parse_module("fn main() { a + b = c; }").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_modules() {
+        let module = parse_module("use a::b;\nfn f() -> f32 { 1.0 }").unwrap();
+        let json = serde_json::to_value(&module).unwrap();
+        let items = &json["items"];
+        assert_eq!(items[0]["kind"]["Use"]["root"], "Source");
+        assert_eq!(items[0]["span"], serde_json::json!({ "start": 0, "end": 9 }));
+        let function = &items[1]["kind"]["Function"];
+        assert_eq!(function["name"]["name"], "f");
+        assert_eq!(function["body"]["tail"]["kind"]["Literal"]["value"]["Float"], serde_json::json!([1.0, null]));
+    }
 }
@@ -0,0 +1,176 @@
+use super::{LiteralValue, TypeName, TypeNameKind};
+
+/// A type provided by the language rather than declared in source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Scalar(Scalar),
+    Vector {
+        scalar: Scalar,
+        size: u8,
+    },
+    Matrix {
+        scalar: Scalar,
+        columns: u8,
+        rows: u8,
+    },
+    /// A combined image sampler, such as `sampler2D` or `samplerCubeShadow`.
+    Sampler {
+        dimension: Dimension,
+        shadow: bool,
+    },
+    /// A separate texture, such as `texture2D`.
+    Texture {
+        dimension: Dimension,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    Bool,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    D1,
+    D2,
+    D3,
+    Cube,
+    D1Array,
+    D2Array,
+    CubeArray,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "bool" => Scalar::Bool,
+            "i32" => Scalar::I32,
+            "u32" => Scalar::U32,
+            "f32" => Scalar::F32,
+            "f64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    /// The scalar of a vector or matrix named with a GLSL style prefix, as in
+    /// `ivec3` or `dmat4`.
+    fn from_prefix(prefix: &str) -> Option<Scalar> {
+        Some(match prefix {
+            "" => Scalar::F32,
+            "b" => Scalar::Bool,
+            "i" => Scalar::I32,
+            "u" => Scalar::U32,
+            "d" => Scalar::F64,
+            _ => return None,
+        })
+    }
+}
+
+impl Dimension {
+    fn from_suffix(suffix: &str) -> Option<Dimension> {
+        Some(match suffix {
+            "1D" => Dimension::D1,
+            "2D" => Dimension::D2,
+            "3D" => Dimension::D3,
+            "Cube" => Dimension::Cube,
+            "1DArray" => Dimension::D1Array,
+            "2DArray" => Dimension::D2Array,
+            "CubeArray" => Dimension::CubeArray,
+            _ => return None,
+        })
+    }
+}
+
+impl Builtin {
+    /// The builtin a type name refers to, if any.
+    pub fn of(type_name: &TypeName) -> Option<Builtin> {
+        match type_name.kind() {
+            TypeNameKind::Identifier(name) => Builtin::from_name(name.str()),
+            TypeNameKind::Generic(name, arguments) => Builtin::from_generic(name.str(), arguments),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Builtin> {
+        if let Some(scalar) = Scalar::from_name(name) {
+            return Some(Builtin::Scalar(scalar));
+        }
+        if let Some(dimension) = name.strip_prefix("sampler") {
+            let (dimension, shadow) = match dimension.strip_suffix("Shadow") {
+                Some(dimension) => (dimension, true),
+                None => (dimension, false),
+            };
+            let dimension = Dimension::from_suffix(dimension)?;
+            if shadow && dimension == Dimension::D3 {
+                return None;
+            }
+            return Some(Builtin::Sampler { dimension, shadow });
+        }
+        if let Some(dimension) = name.strip_prefix("texture") {
+            return Some(Builtin::Texture { dimension: Dimension::from_suffix(dimension)? });
+        }
+
+        if let Some(split) = name.find("vec") {
+            let scalar = Scalar::from_prefix(&name[..split])?;
+            let size = vector_size(&name[split + 3..])?;
+            return Some(Builtin::Vector { scalar, size });
+        }
+        if let Some(split) = name.find("mat") {
+            let scalar = Scalar::from_prefix(&name[..split]).filter(|s| matches!(s, Scalar::F32 | Scalar::F64))?;
+            let size = &name[split + 3..];
+            let (columns, rows) = match size.split_once('x') {
+                Some((columns, rows)) => (vector_size(columns)?, vector_size(rows)?),
+                None => (vector_size(size)?, vector_size(size)?),
+            };
+            return Some(Builtin::Matrix { scalar, columns, rows });
+        }
+        None
+    }
+
+    /// `vec<T, N>`, `mat<T, N>` or `mat<T, C, R>`.
+    fn from_generic(name: &str, arguments: &[TypeName]) -> Option<Builtin> {
+        let scalar = |t: &TypeName| match Builtin::of(t) {
+            Some(Builtin::Scalar(scalar)) => Some(scalar),
+            _ => None,
+        };
+        match (name, arguments) {
+            ("vec", [t, n]) => Some(Builtin::Vector {
+                scalar: scalar(t)?,
+                size: size_argument(n)?,
+            }),
+            ("mat", [t, n]) => {
+                let size = size_argument(n)?;
+                Some(Builtin::Matrix { scalar: scalar(t)?, columns: size, rows: size })
+            },
+            ("mat", [t, c, r]) => Some(Builtin::Matrix {
+                scalar: scalar(t)?,
+                columns: size_argument(c)?,
+                rows: size_argument(r)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn vector_size(text: &str) -> Option<u8> {
+    match text {
+        "2" => Some(2),
+        "3" => Some(3),
+        "4" => Some(4),
+        _ => None,
+    }
+}
+
+fn size_argument(argument: &TypeName) -> Option<u8> {
+    match argument.kind() {
+        TypeNameKind::Literal(literal) => match literal.value() {
+            LiteralValue::Integer(size @ 2..=4, None) => Some(*size as u8),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,24 @@
+use crate::span::ByteSpan;
+
+use super::visit::VisitMut;
+
+/// Moves nodes to a new position in the source, when an edit before them
+/// changes the length of the text.
+pub(super) struct Shift {
+    delta: isize,
+}
+
+impl Shift {
+    pub(super) fn new(delta: isize) -> Shift {
+        Shift {
+            delta,
+        }
+    }
+}
+
+impl VisitMut for Shift {
+    fn visit_span_mut(&mut self, span: &mut ByteSpan) {
+        span.start = span.start.checked_add_signed(self.delta).unwrap();
+        span.end = span.end.checked_add_signed(self.delta).unwrap();
+    }
+}
@@ -0,0 +1,1238 @@
+//! Traversal of the syntax tree.
+//!
+//! `Visit` walks a tree by reference, `VisitMut` walks it mutably, and `Fold`
+//! takes it apart and builds a new one. Each method of a trait visits one
+//! kind of node; by default it calls the `walk_` (or `fold_`) function of
+//! the same name here, which visits each child of the node in source order.
+//! An implementation overrides the methods for the nodes it cares about,
+//! and calls the walk function itself to carry on into their children.
+//! Every span in the tree is passed to `visit_span`. A `Fold` outside this
+//! crate builds nodes with their `new` constructors, and takes expressions,
+//! statements and items apart with `into_kind`.
+
+use crate::span::ByteSpan;
+
+use super::*;
+
+pub trait Visit<'ast> {
+    fn visit_module(&mut self, module: &'ast Module) {
+        walk_module(self, module)
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_doc(&mut self, doc: &'ast Doc) {
+        walk_doc(self, doc)
+    }
+
+    fn visit_attribute(&mut self, attribute: &'ast Attribute) {
+        walk_attribute(self, attribute)
+    }
+
+    fn visit_use(&mut self, item: &'ast Use) {
+        walk_use(self, item)
+    }
+
+    fn visit_use_tree(&mut self, tree: &'ast UseTree) {
+        walk_use_tree(self, tree)
+    }
+
+    fn visit_declare(&mut self, declare: &'ast Declare) {
+        walk_declare(self, declare)
+    }
+
+    fn visit_declare_function(&mut self, function: &'ast DeclareFunction) {
+        walk_declare_function(self, function)
+    }
+
+    fn visit_declare_type(&mut self, declare: &'ast DeclareType) {
+        walk_declare_type(self, declare)
+    }
+
+    fn visit_declare_const(&mut self, declare: &'ast DeclareConst) {
+        walk_declare_const(self, declare)
+    }
+
+    fn visit_global(&mut self, global: &'ast Global) {
+        walk_global(self, global)
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_struct(&mut self, item: &'ast Struct) {
+        walk_struct(self, item)
+    }
+
+    fn visit_arguments(&mut self, arguments: &'ast Arguments) {
+        walk_arguments(self, arguments)
+    }
+
+    fn visit_type_name(&mut self, type_name: &'ast TypeName) {
+        walk_type_name(self, type_name)
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_let(&mut self, binding: &'ast Let) {
+        walk_let(self, binding)
+    }
+
+    fn visit_assignment(&mut self, assignment: &'ast Assignment) {
+        walk_assignment(self, assignment)
+    }
+
+    fn visit_for(&mut self, for_loop: &'ast For) {
+        walk_for(self, for_loop)
+    }
+
+    fn visit_range(&mut self, range: &'ast Range) {
+        walk_range(self, range)
+    }
+
+    fn visit_while(&mut self, while_loop: &'ast While) {
+        walk_while(self, while_loop)
+    }
+
+    fn visit_if(&mut self, if_expr: &'ast If) {
+        walk_if(self, if_expr)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_literal(&mut self, literal: &'ast Literal) {
+        walk_literal(self, literal)
+    }
+
+    fn visit_identifier(&mut self, identifier: &'ast Identifier) {
+        walk_identifier(self, identifier)
+    }
+
+    fn visit_span(&mut self, _span: ByteSpan) {}
+}
+
+pub fn walk_module<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, module: &'ast Module) {
+    for item in &module.items {
+        v.visit_item(item);
+    }
+    v.visit_span(module.span);
+}
+
+pub fn walk_item<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, item: &'ast Item) {
+    for doc in &item.docs {
+        v.visit_doc(doc);
+    }
+    for attribute in &item.attributes {
+        v.visit_attribute(attribute);
+    }
+    match &item.kind {
+        ItemKind::Use(item) => v.visit_use(item),
+        ItemKind::Declare(declare) => v.visit_declare(declare),
+        ItemKind::Global(global) => v.visit_global(global),
+        ItemKind::Function(function) => v.visit_function(function),
+        ItemKind::Struct(item) => v.visit_struct(item),
+        ItemKind::Error => {},
+    }
+    v.visit_span(item.span);
+}
+
+pub fn walk_doc<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, doc: &'ast Doc) {
+    v.visit_span(doc.span);
+}
+
+pub fn walk_attribute<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, attribute: &'ast Attribute) {
+    v.visit_identifier(&attribute.name);
+    for argument in attribute.arguments.iter().flatten() {
+        v.visit_expr(argument);
+    }
+    v.visit_span(attribute.span);
+}
+
+pub fn walk_use<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, item: &'ast Use) {
+    v.visit_use_tree(&item.tree);
+    v.visit_span(item.span);
+}
+
+pub fn walk_use_tree<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, tree: &'ast UseTree) {
+    for name in &tree.path {
+        v.visit_identifier(name);
+    }
+    match &tree.kind {
+        UseTreeKind::Simple(Some(alias)) => v.visit_identifier(alias),
+        UseTreeKind::Simple(None) | UseTreeKind::Glob => {},
+        UseTreeKind::Group(trees) => {
+            for tree in trees {
+                v.visit_use_tree(tree);
+            }
+        },
+    }
+    v.visit_span(tree.span);
+}
+
+pub fn walk_declare<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, declare: &'ast Declare) {
+    match declare {
+        Declare::Function(function) => v.visit_declare_function(function),
+        Declare::Type(declare) => v.visit_declare_type(declare),
+        Declare::Const(declare) => v.visit_declare_const(declare),
+    }
+}
+
+pub fn walk_declare_function<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, function: &'ast DeclareFunction) {
+    v.visit_identifier(&function.name);
+    v.visit_arguments(&function.arguments);
+    v.visit_type_name(&function.return_type);
+    v.visit_span(function.span);
+}
+
+pub fn walk_declare_type<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, declare: &'ast DeclareType) {
+    v.visit_identifier(&declare.name);
+    v.visit_span(declare.span);
+}
+
+pub fn walk_declare_const<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, declare: &'ast DeclareConst) {
+    v.visit_identifier(&declare.name);
+    v.visit_type_name(&declare.type_name);
+    v.visit_span(declare.span);
+}
+
+pub fn walk_global<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, global: &'ast Global) {
+    v.visit_identifier(&global.identifier);
+    v.visit_type_name(&global.definition);
+    if let Some(initializer) = &global.initializer {
+        v.visit_expr(initializer);
+    }
+    v.visit_span(global.span);
+}
+
+pub fn walk_function<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, function: &'ast Function) {
+    v.visit_identifier(&function.name);
+    v.visit_arguments(&function.arguments);
+    if let Some(return_type) = &function.return_type {
+        v.visit_type_name(return_type);
+    }
+    v.visit_block(&function.body);
+    v.visit_span(function.span);
+}
+
+pub fn walk_struct<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, item: &'ast Struct) {
+    v.visit_identifier(&item.name);
+    for (name, definition) in &item.fields {
+        v.visit_identifier(name);
+        v.visit_type_name(definition);
+    }
+    v.visit_span(item.span);
+}
+
+pub fn walk_arguments<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, arguments: &'ast Arguments) {
+    for (name, definition) in &arguments.arguments {
+        v.visit_identifier(name);
+        v.visit_type_name(definition);
+    }
+    v.visit_span(arguments.span);
+}
+
+pub fn walk_type_name<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, type_name: &'ast TypeName) {
+    match &type_name.kind {
+        TypeNameKind::Identifier(name) => v.visit_identifier(name),
+        TypeNameKind::Tuple(types) => {
+            for element in types {
+                v.visit_type_name(element);
+            }
+        },
+        TypeNameKind::Literal(literal) => v.visit_literal(literal),
+        TypeNameKind::Array(element, size) => {
+            v.visit_type_name(element);
+            if let Some(size) = size {
+                v.visit_expr(size);
+            }
+        },
+        TypeNameKind::Generic(name, arguments) => {
+            v.visit_identifier(name);
+            for argument in arguments {
+                v.visit_type_name(argument);
+            }
+        },
+    }
+    v.visit_span(type_name.span);
+}
+
+pub fn walk_block<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, block: &'ast Block) {
+    for statement in &block.statements {
+        v.visit_statement(statement);
+    }
+    if let Some(tail) = &block.tail {
+        v.visit_expr(tail);
+    }
+    v.visit_span(block.span);
+}
+
+pub fn walk_statement<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, statement: &'ast Statement) {
+    match &statement.kind {
+        StatementKind::Let(binding) => v.visit_let(binding),
+        StatementKind::Assignment(assignment) => v.visit_assignment(assignment),
+        StatementKind::Block(block) => v.visit_block(block),
+        StatementKind::Expr(expr) => v.visit_expr(expr),
+        StatementKind::For(for_loop) => v.visit_for(for_loop),
+        StatementKind::While(while_loop) => v.visit_while(while_loop),
+        StatementKind::Return(Some(value)) => v.visit_expr(value),
+        StatementKind::Return(None) | StatementKind::Break | StatementKind::Continue => {},
+    }
+    v.visit_span(statement.span);
+}
+
+pub fn walk_let<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, binding: &'ast Let) {
+    v.visit_identifier(&binding.binding);
+    if let Some(type_name) = &binding.type_name {
+        v.visit_type_name(type_name);
+    }
+    if let Some(expression) = &binding.expression {
+        v.visit_expr(expression);
+    }
+    v.visit_span(binding.span);
+}
+
+pub fn walk_assignment<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, assignment: &'ast Assignment) {
+    v.visit_expr(&assignment.target);
+    v.visit_expr(&assignment.expression);
+    v.visit_span(assignment.span);
+}
+
+pub fn walk_for<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, for_loop: &'ast For) {
+    v.visit_identifier(&for_loop.binding);
+    v.visit_range(&for_loop.range);
+    v.visit_block(&for_loop.body);
+    v.visit_span(for_loop.span);
+}
+
+pub fn walk_range<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, range: &'ast Range) {
+    v.visit_expr(&range.start);
+    v.visit_expr(&range.end);
+    v.visit_span(range.span);
+}
+
+pub fn walk_while<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, while_loop: &'ast While) {
+    v.visit_expr(&while_loop.condition);
+    v.visit_block(&while_loop.body);
+    v.visit_span(while_loop.span);
+}
+
+pub fn walk_if<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, if_expr: &'ast If) {
+    v.visit_expr(&if_expr.condition);
+    v.visit_block(&if_expr.then);
+    match &if_expr.otherwise {
+        Some(Else::If(next)) => v.visit_if(next),
+        Some(Else::Block(block)) => v.visit_block(block),
+        None => {},
+    }
+    v.visit_span(if_expr.span);
+}
+
+pub fn walk_expr<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, expr: &'ast Expr) {
+    match &expr.kind {
+        ExprKind::Literal(literal) => v.visit_literal(literal),
+        ExprKind::Identifier(name) => v.visit_identifier(name),
+        ExprKind::Unary(_, operand) => v.visit_expr(operand),
+        ExprKind::Binary(_, left, right) => {
+            v.visit_expr(left);
+            v.visit_expr(right);
+        },
+        ExprKind::Group(inner) => v.visit_expr(inner),
+        ExprKind::Call(callee, arguments) => {
+            v.visit_expr(callee);
+            for argument in arguments {
+                v.visit_expr(argument);
+            }
+        },
+        ExprKind::Field(base, field) => {
+            v.visit_expr(base);
+            v.visit_identifier(field);
+        },
+        ExprKind::Index(base, index) => {
+            v.visit_expr(base);
+            v.visit_expr(index);
+        },
+        ExprKind::Struct(name, fields) => {
+            v.visit_identifier(name);
+            for (field, value) in fields {
+                v.visit_identifier(field);
+                v.visit_expr(value);
+            }
+        },
+        ExprKind::If(if_expr) => v.visit_if(if_expr),
+    }
+    v.visit_span(expr.span);
+}
+
+pub fn walk_literal<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, literal: &'ast Literal) {
+    v.visit_span(literal.span);
+}
+
+pub fn walk_identifier<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, identifier: &'ast Identifier) {
+    v.visit_span(identifier.span);
+}
+
+pub trait VisitMut {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_doc_mut(&mut self, doc: &mut Doc) {
+        walk_doc_mut(self, doc)
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute) {
+        walk_attribute_mut(self, attribute)
+    }
+
+    fn visit_use_mut(&mut self, item: &mut Use) {
+        walk_use_mut(self, item)
+    }
+
+    fn visit_use_tree_mut(&mut self, tree: &mut UseTree) {
+        walk_use_tree_mut(self, tree)
+    }
+
+    fn visit_declare_mut(&mut self, declare: &mut Declare) {
+        walk_declare_mut(self, declare)
+    }
+
+    fn visit_declare_function_mut(&mut self, function: &mut DeclareFunction) {
+        walk_declare_function_mut(self, function)
+    }
+
+    fn visit_declare_type_mut(&mut self, declare: &mut DeclareType) {
+        walk_declare_type_mut(self, declare)
+    }
+
+    fn visit_declare_const_mut(&mut self, declare: &mut DeclareConst) {
+        walk_declare_const_mut(self, declare)
+    }
+
+    fn visit_global_mut(&mut self, global: &mut Global) {
+        walk_global_mut(self, global)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_struct_mut(&mut self, item: &mut Struct) {
+        walk_struct_mut(self, item)
+    }
+
+    fn visit_arguments_mut(&mut self, arguments: &mut Arguments) {
+        walk_arguments_mut(self, arguments)
+    }
+
+    fn visit_type_name_mut(&mut self, type_name: &mut TypeName) {
+        walk_type_name_mut(self, type_name)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_let_mut(&mut self, binding: &mut Let) {
+        walk_let_mut(self, binding)
+    }
+
+    fn visit_assignment_mut(&mut self, assignment: &mut Assignment) {
+        walk_assignment_mut(self, assignment)
+    }
+
+    fn visit_for_mut(&mut self, for_loop: &mut For) {
+        walk_for_mut(self, for_loop)
+    }
+
+    fn visit_range_mut(&mut self, range: &mut Range) {
+        walk_range_mut(self, range)
+    }
+
+    fn visit_while_mut(&mut self, while_loop: &mut While) {
+        walk_while_mut(self, while_loop)
+    }
+
+    fn visit_if_mut(&mut self, if_expr: &mut If) {
+        walk_if_mut(self, if_expr)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_literal_mut(&mut self, literal: &mut Literal) {
+        walk_literal_mut(self, literal)
+    }
+
+    fn visit_identifier_mut(&mut self, identifier: &mut Identifier) {
+        walk_identifier_mut(self, identifier)
+    }
+
+    fn visit_span_mut(&mut self, _span: &mut ByteSpan) {}
+}
+
+pub fn walk_module_mut<V: VisitMut + ?Sized>(v: &mut V, module: &mut Module) {
+    for item in &mut module.items {
+        v.visit_item_mut(item);
+    }
+    v.visit_span_mut(&mut module.span);
+}
+
+pub fn walk_item_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut Item) {
+    for doc in &mut item.docs {
+        v.visit_doc_mut(doc);
+    }
+    for attribute in &mut item.attributes {
+        v.visit_attribute_mut(attribute);
+    }
+    match &mut item.kind {
+        ItemKind::Use(item) => v.visit_use_mut(item),
+        ItemKind::Declare(declare) => v.visit_declare_mut(declare),
+        ItemKind::Global(global) => v.visit_global_mut(global),
+        ItemKind::Function(function) => v.visit_function_mut(function),
+        ItemKind::Struct(item) => v.visit_struct_mut(item),
+        ItemKind::Error => {},
+    }
+    v.visit_span_mut(&mut item.span);
+}
+
+pub fn walk_doc_mut<V: VisitMut + ?Sized>(v: &mut V, doc: &mut Doc) {
+    v.visit_span_mut(&mut doc.span);
+}
+
+pub fn walk_attribute_mut<V: VisitMut + ?Sized>(v: &mut V, attribute: &mut Attribute) {
+    v.visit_identifier_mut(&mut attribute.name);
+    for argument in attribute.arguments.iter_mut().flatten() {
+        v.visit_expr_mut(argument);
+    }
+    v.visit_span_mut(&mut attribute.span);
+}
+
+pub fn walk_use_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut Use) {
+    v.visit_use_tree_mut(&mut item.tree);
+    v.visit_span_mut(&mut item.span);
+}
+
+pub fn walk_use_tree_mut<V: VisitMut + ?Sized>(v: &mut V, tree: &mut UseTree) {
+    for name in &mut tree.path {
+        v.visit_identifier_mut(name);
+    }
+    match &mut tree.kind {
+        UseTreeKind::Simple(Some(alias)) => v.visit_identifier_mut(alias),
+        UseTreeKind::Simple(None) | UseTreeKind::Glob => {},
+        UseTreeKind::Group(trees) => {
+            for tree in trees {
+                v.visit_use_tree_mut(tree);
+            }
+        },
+    }
+    v.visit_span_mut(&mut tree.span);
+}
+
+pub fn walk_declare_mut<V: VisitMut + ?Sized>(v: &mut V, declare: &mut Declare) {
+    match declare {
+        Declare::Function(function) => v.visit_declare_function_mut(function),
+        Declare::Type(declare) => v.visit_declare_type_mut(declare),
+        Declare::Const(declare) => v.visit_declare_const_mut(declare),
+    }
+}
+
+pub fn walk_declare_function_mut<V: VisitMut + ?Sized>(v: &mut V, function: &mut DeclareFunction) {
+    v.visit_identifier_mut(&mut function.name);
+    v.visit_arguments_mut(&mut function.arguments);
+    v.visit_type_name_mut(&mut function.return_type);
+    v.visit_span_mut(&mut function.span);
+}
+
+pub fn walk_declare_type_mut<V: VisitMut + ?Sized>(v: &mut V, declare: &mut DeclareType) {
+    v.visit_identifier_mut(&mut declare.name);
+    v.visit_span_mut(&mut declare.span);
+}
+
+pub fn walk_declare_const_mut<V: VisitMut + ?Sized>(v: &mut V, declare: &mut DeclareConst) {
+    v.visit_identifier_mut(&mut declare.name);
+    v.visit_type_name_mut(&mut declare.type_name);
+    v.visit_span_mut(&mut declare.span);
+}
+
+pub fn walk_global_mut<V: VisitMut + ?Sized>(v: &mut V, global: &mut Global) {
+    v.visit_identifier_mut(&mut global.identifier);
+    v.visit_type_name_mut(&mut global.definition);
+    if let Some(initializer) = &mut global.initializer {
+        v.visit_expr_mut(initializer);
+    }
+    v.visit_span_mut(&mut global.span);
+}
+
+pub fn walk_function_mut<V: VisitMut + ?Sized>(v: &mut V, function: &mut Function) {
+    v.visit_identifier_mut(&mut function.name);
+    v.visit_arguments_mut(&mut function.arguments);
+    if let Some(return_type) = &mut function.return_type {
+        v.visit_type_name_mut(return_type);
+    }
+    v.visit_block_mut(&mut function.body);
+    v.visit_span_mut(&mut function.span);
+}
+
+pub fn walk_struct_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut Struct) {
+    v.visit_identifier_mut(&mut item.name);
+    for (name, definition) in &mut item.fields {
+        v.visit_identifier_mut(name);
+        v.visit_type_name_mut(definition);
+    }
+    v.visit_span_mut(&mut item.span);
+}
+
+pub fn walk_arguments_mut<V: VisitMut + ?Sized>(v: &mut V, arguments: &mut Arguments) {
+    for (name, definition) in &mut arguments.arguments {
+        v.visit_identifier_mut(name);
+        v.visit_type_name_mut(definition);
+    }
+    v.visit_span_mut(&mut arguments.span);
+}
+
+pub fn walk_type_name_mut<V: VisitMut + ?Sized>(v: &mut V, type_name: &mut TypeName) {
+    match &mut type_name.kind {
+        TypeNameKind::Identifier(name) => v.visit_identifier_mut(name),
+        TypeNameKind::Tuple(types) => {
+            for element in types {
+                v.visit_type_name_mut(element);
+            }
+        },
+        TypeNameKind::Literal(literal) => v.visit_literal_mut(literal),
+        TypeNameKind::Array(element, size) => {
+            v.visit_type_name_mut(element);
+            if let Some(size) = size {
+                v.visit_expr_mut(size);
+            }
+        },
+        TypeNameKind::Generic(name, arguments) => {
+            v.visit_identifier_mut(name);
+            for argument in arguments {
+                v.visit_type_name_mut(argument);
+            }
+        },
+    }
+    v.visit_span_mut(&mut type_name.span);
+}
+
+pub fn walk_block_mut<V: VisitMut + ?Sized>(v: &mut V, block: &mut Block) {
+    for statement in &mut block.statements {
+        v.visit_statement_mut(statement);
+    }
+    if let Some(tail) = &mut block.tail {
+        v.visit_expr_mut(tail);
+    }
+    v.visit_span_mut(&mut block.span);
+}
+
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(v: &mut V, statement: &mut Statement) {
+    match &mut statement.kind {
+        StatementKind::Let(binding) => v.visit_let_mut(binding),
+        StatementKind::Assignment(assignment) => v.visit_assignment_mut(assignment),
+        StatementKind::Block(block) => v.visit_block_mut(block),
+        StatementKind::Expr(expr) => v.visit_expr_mut(expr),
+        StatementKind::For(for_loop) => v.visit_for_mut(for_loop),
+        StatementKind::While(while_loop) => v.visit_while_mut(while_loop),
+        StatementKind::Return(Some(value)) => v.visit_expr_mut(value),
+        StatementKind::Return(None) | StatementKind::Break | StatementKind::Continue => {},
+    }
+    v.visit_span_mut(&mut statement.span);
+}
+
+pub fn walk_let_mut<V: VisitMut + ?Sized>(v: &mut V, binding: &mut Let) {
+    v.visit_identifier_mut(&mut binding.binding);
+    if let Some(type_name) = &mut binding.type_name {
+        v.visit_type_name_mut(type_name);
+    }
+    if let Some(expression) = &mut binding.expression {
+        v.visit_expr_mut(expression);
+    }
+    v.visit_span_mut(&mut binding.span);
+}
+
+pub fn walk_assignment_mut<V: VisitMut + ?Sized>(v: &mut V, assignment: &mut Assignment) {
+    v.visit_expr_mut(&mut assignment.target);
+    v.visit_expr_mut(&mut assignment.expression);
+    v.visit_span_mut(&mut assignment.span);
+}
+
+pub fn walk_for_mut<V: VisitMut + ?Sized>(v: &mut V, for_loop: &mut For) {
+    v.visit_identifier_mut(&mut for_loop.binding);
+    v.visit_range_mut(&mut for_loop.range);
+    v.visit_block_mut(&mut for_loop.body);
+    v.visit_span_mut(&mut for_loop.span);
+}
+
+pub fn walk_range_mut<V: VisitMut + ?Sized>(v: &mut V, range: &mut Range) {
+    v.visit_expr_mut(&mut range.start);
+    v.visit_expr_mut(&mut range.end);
+    v.visit_span_mut(&mut range.span);
+}
+
+pub fn walk_while_mut<V: VisitMut + ?Sized>(v: &mut V, while_loop: &mut While) {
+    v.visit_expr_mut(&mut while_loop.condition);
+    v.visit_block_mut(&mut while_loop.body);
+    v.visit_span_mut(&mut while_loop.span);
+}
+
+pub fn walk_if_mut<V: VisitMut + ?Sized>(v: &mut V, if_expr: &mut If) {
+    v.visit_expr_mut(&mut if_expr.condition);
+    v.visit_block_mut(&mut if_expr.then);
+    match &mut if_expr.otherwise {
+        Some(Else::If(next)) => v.visit_if_mut(next),
+        Some(Else::Block(block)) => v.visit_block_mut(block),
+        None => {},
+    }
+    v.visit_span_mut(&mut if_expr.span);
+}
+
+pub fn walk_expr_mut<V: VisitMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Literal(literal) => v.visit_literal_mut(literal),
+        ExprKind::Identifier(name) => v.visit_identifier_mut(name),
+        ExprKind::Unary(_, operand) => v.visit_expr_mut(operand),
+        ExprKind::Binary(_, left, right) => {
+            v.visit_expr_mut(left);
+            v.visit_expr_mut(right);
+        },
+        ExprKind::Group(inner) => v.visit_expr_mut(inner),
+        ExprKind::Call(callee, arguments) => {
+            v.visit_expr_mut(callee);
+            for argument in arguments {
+                v.visit_expr_mut(argument);
+            }
+        },
+        ExprKind::Field(base, field) => {
+            v.visit_expr_mut(base);
+            v.visit_identifier_mut(field);
+        },
+        ExprKind::Index(base, index) => {
+            v.visit_expr_mut(base);
+            v.visit_expr_mut(index);
+        },
+        ExprKind::Struct(name, fields) => {
+            v.visit_identifier_mut(name);
+            for (field, value) in fields {
+                v.visit_identifier_mut(field);
+                v.visit_expr_mut(value);
+            }
+        },
+        ExprKind::If(if_expr) => v.visit_if_mut(if_expr),
+    }
+    v.visit_span_mut(&mut expr.span);
+}
+
+pub fn walk_literal_mut<V: VisitMut + ?Sized>(v: &mut V, literal: &mut Literal) {
+    v.visit_span_mut(&mut literal.span);
+}
+
+pub fn walk_identifier_mut<V: VisitMut + ?Sized>(v: &mut V, identifier: &mut Identifier) {
+    v.visit_span_mut(&mut identifier.span);
+}
+
+pub trait Fold {
+    fn fold_module(&mut self, module: Module) -> Module {
+        fold_module(self, module)
+    }
+
+    fn fold_item(&mut self, item: Item) -> Item {
+        fold_item(self, item)
+    }
+
+    fn fold_doc(&mut self, doc: Doc) -> Doc {
+        fold_doc(self, doc)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute) -> Attribute {
+        fold_attribute(self, attribute)
+    }
+
+    fn fold_use(&mut self, item: Use) -> Use {
+        fold_use(self, item)
+    }
+
+    fn fold_use_tree(&mut self, tree: UseTree) -> UseTree {
+        fold_use_tree(self, tree)
+    }
+
+    fn fold_declare(&mut self, declare: Declare) -> Declare {
+        fold_declare(self, declare)
+    }
+
+    fn fold_declare_function(&mut self, function: DeclareFunction) -> DeclareFunction {
+        fold_declare_function(self, function)
+    }
+
+    fn fold_declare_type(&mut self, declare: DeclareType) -> DeclareType {
+        fold_declare_type(self, declare)
+    }
+
+    fn fold_declare_const(&mut self, declare: DeclareConst) -> DeclareConst {
+        fold_declare_const(self, declare)
+    }
+
+    fn fold_global(&mut self, global: Global) -> Global {
+        fold_global(self, global)
+    }
+
+    fn fold_function(&mut self, function: Function) -> Function {
+        fold_function(self, function)
+    }
+
+    fn fold_struct(&mut self, item: Struct) -> Struct {
+        fold_struct(self, item)
+    }
+
+    fn fold_arguments(&mut self, arguments: Arguments) -> Arguments {
+        fold_arguments(self, arguments)
+    }
+
+    fn fold_type_name(&mut self, type_name: TypeName) -> TypeName {
+        fold_type_name(self, type_name)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        fold_block(self, block)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        fold_statement(self, statement)
+    }
+
+    fn fold_let(&mut self, binding: Let) -> Let {
+        fold_let(self, binding)
+    }
+
+    fn fold_assignment(&mut self, assignment: Assignment) -> Assignment {
+        fold_assignment(self, assignment)
+    }
+
+    fn fold_for(&mut self, for_loop: For) -> For {
+        fold_for(self, for_loop)
+    }
+
+    fn fold_range(&mut self, range: Range) -> Range {
+        fold_range(self, range)
+    }
+
+    fn fold_while(&mut self, while_loop: While) -> While {
+        fold_while(self, while_loop)
+    }
+
+    fn fold_if(&mut self, if_expr: If) -> If {
+        fold_if(self, if_expr)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        fold_literal(self, literal)
+    }
+
+    fn fold_identifier(&mut self, identifier: Identifier) -> Identifier {
+        fold_identifier(self, identifier)
+    }
+
+    fn fold_span(&mut self, span: ByteSpan) -> ByteSpan {
+        span
+    }
+}
+
+pub fn fold_module<F: Fold + ?Sized>(f: &mut F, module: Module) -> Module {
+    Module {
+        items: module.items.into_iter().map(|item| f.fold_item(item)).collect(),
+        ids: module.ids,
+        span: f.fold_span(module.span),
+    }
+}
+
+pub fn fold_item<F: Fold + ?Sized>(f: &mut F, item: Item) -> Item {
+    let docs = item.docs.into_iter().map(|doc| f.fold_doc(doc)).collect();
+    let attributes = item.attributes.into_iter().map(|attribute| f.fold_attribute(attribute)).collect();
+    let kind = match item.kind {
+        ItemKind::Use(item) => ItemKind::Use(f.fold_use(item)),
+        ItemKind::Declare(declare) => ItemKind::Declare(f.fold_declare(declare)),
+        ItemKind::Global(global) => ItemKind::Global(f.fold_global(global)),
+        ItemKind::Function(function) => ItemKind::Function(f.fold_function(function)),
+        ItemKind::Struct(item) => ItemKind::Struct(f.fold_struct(item)),
+        ItemKind::Error => ItemKind::Error,
+    };
+    Item {
+        kind,
+        visibility: item.visibility,
+        docs,
+        attributes,
+        span: f.fold_span(item.span),
+    }
+}
+
+pub fn fold_doc<F: Fold + ?Sized>(f: &mut F, doc: Doc) -> Doc {
+    Doc {
+        text: doc.text,
+        span: f.fold_span(doc.span),
+    }
+}
+
+pub fn fold_attribute<F: Fold + ?Sized>(f: &mut F, attribute: Attribute) -> Attribute {
+    Attribute {
+        name: f.fold_identifier(attribute.name),
+        arguments: attribute.arguments
+            .map(|arguments| arguments.into_iter().map(|argument| f.fold_expr(argument)).collect()),
+        span: f.fold_span(attribute.span),
+    }
+}
+
+pub fn fold_use<F: Fold + ?Sized>(f: &mut F, item: Use) -> Use {
+    Use {
+        root: item.root,
+        tree: f.fold_use_tree(item.tree),
+        span: f.fold_span(item.span),
+    }
+}
+
+pub fn fold_use_tree<F: Fold + ?Sized>(f: &mut F, tree: UseTree) -> UseTree {
+    let path = tree.path.into_iter().map(|name| f.fold_identifier(name)).collect();
+    let kind = match tree.kind {
+        UseTreeKind::Simple(alias) => UseTreeKind::Simple(alias.map(|alias| f.fold_identifier(alias))),
+        UseTreeKind::Glob => UseTreeKind::Glob,
+        UseTreeKind::Group(trees) => UseTreeKind::Group(trees.into_iter().map(|tree| f.fold_use_tree(tree)).collect()),
+    };
+    UseTree {
+        path,
+        kind,
+        span: f.fold_span(tree.span),
+    }
+}
+
+pub fn fold_declare<F: Fold + ?Sized>(f: &mut F, declare: Declare) -> Declare {
+    match declare {
+        Declare::Function(function) => Declare::Function(f.fold_declare_function(function)),
+        Declare::Type(declare) => Declare::Type(f.fold_declare_type(declare)),
+        Declare::Const(declare) => Declare::Const(f.fold_declare_const(declare)),
+    }
+}
+
+pub fn fold_declare_function<F: Fold + ?Sized>(f: &mut F, function: DeclareFunction) -> DeclareFunction {
+    DeclareFunction {
+        name: f.fold_identifier(function.name),
+        arguments: f.fold_arguments(function.arguments),
+        return_type: f.fold_type_name(function.return_type),
+        span: f.fold_span(function.span),
+    }
+}
+
+pub fn fold_declare_type<F: Fold + ?Sized>(f: &mut F, declare: DeclareType) -> DeclareType {
+    DeclareType {
+        name: f.fold_identifier(declare.name),
+        span: f.fold_span(declare.span),
+    }
+}
+
+pub fn fold_declare_const<F: Fold + ?Sized>(f: &mut F, declare: DeclareConst) -> DeclareConst {
+    DeclareConst {
+        name: f.fold_identifier(declare.name),
+        type_name: f.fold_type_name(declare.type_name),
+        span: f.fold_span(declare.span),
+    }
+}
+
+pub fn fold_global<F: Fold + ?Sized>(f: &mut F, global: Global) -> Global {
+    Global {
+        qualifier: global.qualifier,
+        identifier: f.fold_identifier(global.identifier),
+        definition: f.fold_type_name(global.definition),
+        initializer: global.initializer.map(|initializer| f.fold_expr(initializer)),
+        span: f.fold_span(global.span),
+    }
+}
+
+pub fn fold_function<F: Fold + ?Sized>(f: &mut F, function: Function) -> Function {
+    let name = f.fold_identifier(function.name);
+    let arguments = f.fold_arguments(function.arguments);
+    Function {
+        name,
+        return_type: function.return_type.map(|return_type| f.fold_type_name(return_type)),
+        arguments,
+        body: f.fold_block(function.body),
+        span: f.fold_span(function.span),
+    }
+}
+
+pub fn fold_struct<F: Fold + ?Sized>(f: &mut F, item: Struct) -> Struct {
+    Struct {
+        name: f.fold_identifier(item.name),
+        fields: item.fields.into_iter()
+            .map(|(name, definition)| (f.fold_identifier(name), f.fold_type_name(definition)))
+            .collect(),
+        span: f.fold_span(item.span),
+    }
+}
+
+pub fn fold_arguments<F: Fold + ?Sized>(f: &mut F, arguments: Arguments) -> Arguments {
+    Arguments {
+        arguments: arguments.arguments.into_iter()
+            .map(|(name, definition)| (f.fold_identifier(name), f.fold_type_name(definition)))
+            .collect(),
+        span: f.fold_span(arguments.span),
+    }
+}
+
+pub fn fold_type_name<F: Fold + ?Sized>(f: &mut F, type_name: TypeName) -> TypeName {
+    let kind = match type_name.kind {
+        TypeNameKind::Identifier(name) => TypeNameKind::Identifier(f.fold_identifier(name)),
+        TypeNameKind::Tuple(types) => TypeNameKind::Tuple(types.into_iter().map(|element| f.fold_type_name(element)).collect()),
+        TypeNameKind::Literal(literal) => TypeNameKind::Literal(f.fold_literal(literal)),
+        TypeNameKind::Array(element, size) => {
+            let element = Box::new(f.fold_type_name(*element));
+            TypeNameKind::Array(element, size.map(|size| Box::new(f.fold_expr(*size))))
+        },
+        TypeNameKind::Generic(name, arguments) => {
+            let name = f.fold_identifier(name);
+            TypeNameKind::Generic(name, arguments.into_iter().map(|argument| f.fold_type_name(argument)).collect())
+        },
+    };
+    TypeName {
+        kind,
+        span: f.fold_span(type_name.span),
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(f: &mut F, block: Block) -> Block {
+    Block {
+        statements: block.statements.into_iter().map(|statement| f.fold_statement(statement)).collect(),
+        tail: block.tail.map(|tail| f.fold_expr(tail)),
+        span: f.fold_span(block.span),
+    }
+}
+
+pub fn fold_statement<F: Fold + ?Sized>(f: &mut F, statement: Statement) -> Statement {
+    let kind = match statement.kind {
+        StatementKind::Let(binding) => StatementKind::Let(f.fold_let(binding)),
+        StatementKind::Assignment(assignment) => StatementKind::Assignment(f.fold_assignment(assignment)),
+        StatementKind::Block(block) => StatementKind::Block(f.fold_block(block)),
+        StatementKind::Expr(expr) => StatementKind::Expr(f.fold_expr(expr)),
+        StatementKind::For(for_loop) => StatementKind::For(f.fold_for(for_loop)),
+        StatementKind::While(while_loop) => StatementKind::While(f.fold_while(while_loop)),
+        StatementKind::Return(value) => StatementKind::Return(value.map(|value| f.fold_expr(value))),
+        StatementKind::Break => StatementKind::Break,
+        StatementKind::Continue => StatementKind::Continue,
+    };
+    Statement {
+        kind,
+        span: f.fold_span(statement.span),
+    }
+}
+
+pub fn fold_let<F: Fold + ?Sized>(f: &mut F, binding: Let) -> Let {
+    Let {
+        binding: f.fold_identifier(binding.binding),
+        mutable: binding.mutable,
+        type_name: binding.type_name.map(|type_name| f.fold_type_name(type_name)),
+        expression: binding.expression.map(|expression| f.fold_expr(expression)),
+        span: f.fold_span(binding.span),
+    }
+}
+
+pub fn fold_assignment<F: Fold + ?Sized>(f: &mut F, assignment: Assignment) -> Assignment {
+    Assignment {
+        target: f.fold_expr(assignment.target),
+        operator: assignment.operator,
+        expression: f.fold_expr(assignment.expression),
+        span: f.fold_span(assignment.span),
+    }
+}
+
+pub fn fold_for<F: Fold + ?Sized>(f: &mut F, for_loop: For) -> For {
+    For {
+        binding: f.fold_identifier(for_loop.binding),
+        range: f.fold_range(for_loop.range),
+        body: f.fold_block(for_loop.body),
+        span: f.fold_span(for_loop.span),
+    }
+}
+
+pub fn fold_range<F: Fold + ?Sized>(f: &mut F, range: Range) -> Range {
+    Range {
+        start: f.fold_expr(range.start),
+        end: f.fold_expr(range.end),
+        inclusive: range.inclusive,
+        span: f.fold_span(range.span),
+    }
+}
+
+pub fn fold_while<F: Fold + ?Sized>(f: &mut F, while_loop: While) -> While {
+    While {
+        condition: f.fold_expr(while_loop.condition),
+        body: f.fold_block(while_loop.body),
+        span: f.fold_span(while_loop.span),
+    }
+}
+
+pub fn fold_if<F: Fold + ?Sized>(f: &mut F, if_expr: If) -> If {
+    If {
+        condition: f.fold_expr(if_expr.condition),
+        then: f.fold_block(if_expr.then),
+        otherwise: if_expr.otherwise.map(|otherwise| match otherwise {
+            Else::If(next) => Else::If(Box::new(f.fold_if(*next))),
+            Else::Block(block) => Else::Block(f.fold_block(block)),
+        }),
+        span: f.fold_span(if_expr.span),
+    }
+}
+
+pub fn fold_expr<F: Fold + ?Sized>(f: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Literal(literal) => ExprKind::Literal(f.fold_literal(literal)),
+        ExprKind::Identifier(name) => ExprKind::Identifier(f.fold_identifier(name)),
+        ExprKind::Unary(op, operand) => ExprKind::Unary(op, Box::new(f.fold_expr(*operand))),
+        ExprKind::Binary(op, left, right) => {
+            let left = Box::new(f.fold_expr(*left));
+            ExprKind::Binary(op, left, Box::new(f.fold_expr(*right)))
+        },
+        ExprKind::Group(inner) => ExprKind::Group(Box::new(f.fold_expr(*inner))),
+        ExprKind::Call(callee, arguments) => {
+            let callee = Box::new(f.fold_expr(*callee));
+            ExprKind::Call(callee, arguments.into_iter().map(|argument| f.fold_expr(argument)).collect())
+        },
+        ExprKind::Field(base, field) => {
+            let base = Box::new(f.fold_expr(*base));
+            ExprKind::Field(base, f.fold_identifier(field))
+        },
+        ExprKind::Index(base, index) => {
+            let base = Box::new(f.fold_expr(*base));
+            ExprKind::Index(base, Box::new(f.fold_expr(*index)))
+        },
+        ExprKind::Struct(name, fields) => {
+            let name = f.fold_identifier(name);
+            let fields = fields.into_iter()
+                .map(|(field, value)| (f.fold_identifier(field), f.fold_expr(value)))
+                .collect();
+            ExprKind::Struct(name, fields)
+        },
+        ExprKind::If(if_expr) => ExprKind::If(Box::new(f.fold_if(*if_expr))),
+    };
+    Expr {
+        kind,
+        span: f.fold_span(expr.span),
+    }
+}
+
+pub fn fold_literal<F: Fold + ?Sized>(f: &mut F, literal: Literal) -> Literal {
+    Literal {
+        value: literal.value,
+        text: literal.text,
+        span: f.fold_span(literal.span),
+    }
+}
+
+pub fn fold_identifier<F: Fold + ?Sized>(f: &mut F, identifier: Identifier) -> Identifier {
+    Identifier {
+        name: identifier.name,
+        span: f.fold_span(identifier.span),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = "use a::{b as c};\n\
+        #[location(0)] in position: vec4;\n\
+        fn main(x: f32) -> f32 { let y = (x + 1.0); if y > 2.0 { y } else { f(y).z } }\n";
+
+    #[derive(Default)]
+    struct Names<'ast> {
+        names: Vec<&'ast str>,
+        spans: usize,
+    }
+
+    impl<'ast> Visit<'ast> for Names<'ast> {
+        fn visit_identifier(&mut self, identifier: &'ast Identifier) {
+            self.names.push(identifier.str());
+            walk_identifier(self, identifier);
+        }
+
+        fn visit_span(&mut self, _span: ByteSpan) {
+            self.spans += 1;
+        }
+    }
+
+    #[test]
+    fn visits_nodes_in_order() {
+        let module = parse_module(SOURCE).unwrap();
+        let mut names = Names::default();
+        names.visit_module(&module);
+        assert_eq!(names.names, ["a", "b", "c", "location", "position", "vec4", "main", "x", "f32", "f32", "y", "x", "y", "y", "f", "y", "z"]);
+        assert!(names.spans > names.names.len());
+    }
+
+    struct Rename;
+
+    impl VisitMut for Rename {
+        fn visit_identifier_mut(&mut self, identifier: &mut Identifier) {
+            if identifier.str() == "y" {
+                *identifier = Identifier::new("w".into(), identifier.span());
+            }
+        }
+    }
+
+    #[test]
+    fn visits_nodes_mutably() {
+        let mut module = parse_module(SOURCE).unwrap();
+        Rename.visit_module_mut(&mut module);
+        let mut names = Names::default();
+        names.visit_module(&module);
+        assert_eq!(names.names.iter().filter(|name| **name == "w").count(), 4);
+        assert!(!names.names.contains(&"y"));
+    }
+
+    /// Removes parentheses.
+    struct Ungroup;
+
+    impl Fold for Ungroup {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            match fold_expr(self, expr) {
+                Expr { kind: ExprKind::Group(inner), .. } => *inner,
+                expr => expr,
+            }
+        }
+    }
+
+    #[test]
+    fn folds_nodes() {
+        let module = Ungroup.fold_module(parse_module(SOURCE).unwrap());
+        let function = match module.items()[2].kind() {
+            ItemKind::Function(function) => function,
+            kind => panic!("expected function, found {:?}", kind),
+        };
+        let binding = match function.body().statements()[0].kind() {
+            StatementKind::Let(binding) => binding,
+            kind => panic!("expected let, found {:?}", kind),
+        };
+        let value = binding.expression().unwrap();
+        assert!(matches!(value.kind(), ExprKind::Binary(BinaryOp::Add, _, _)));
+        assert_eq!(value.span(), ByteSpan::new(85, 92));
+    }
+}
@@ -13,6 +13,14 @@ pub enum Token {
     Const,
     #[token("use")]
     Use,
+    #[token("pub")]
+    Pub,
+    #[token("crate")]
+    Crate,
+    #[token("super")]
+    Super,
+    #[token("as")]
+    As,
 
     // variables
     #[token("in")]
@@ -27,6 +35,8 @@ pub enum Token {
     Mut,
 
     // types
+    #[token("struct")]
+    Struct,
 
     // functions
     #[token("fn")]
@@ -37,8 +47,22 @@ pub enum Token {
     // control flow
     #[token("if")]
     If,
+    #[token("else")]
+    Else,
     #[token("for")]
     For,
+    #[token("while")]
+    While,
+    #[token("return")]
+    Return,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("..")]
+    Range,
+    #[token("..=")]
+    RangeInclusive,
 
     // symbols
     #[token(".")]
@@ -57,36 +81,164 @@ pub enum Token {
     RightBrace,
     #[token("::")]
     PathSeparator,
+    #[token(",")]
+    Comma,
+    #[token("[")]
+    LeftBracket,
+    #[token("]")]
+    RightBracket,
+    #[token("#")]
+    Pound,
 
     // operators
     #[token("=")]
     Equals,
+    #[token("+")]
+    Plus,
+    #[token("-")]
+    Minus,
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+    #[token("%")]
+    Percent,
+    #[token("!")]
+    Bang,
+    #[token("&")]
+    Ampersand,
+    #[token("|")]
+    Pipe,
+    #[token("^")]
+    Caret,
+    #[token("<<")]
+    ShiftLeft,
+    #[token(">>")]
+    ShiftRight,
+    #[token("&&")]
+    And,
+    #[token("||")]
+    Or,
+    #[token("==")]
+    EqualsEquals,
+    #[token("!=")]
+    NotEquals,
+    #[token("<")]
+    LessThan,
+    #[token("<=")]
+    LessThanEquals,
+    #[token(">")]
+    GreaterThan,
+    #[token(">=")]
+    GreaterThanEquals,
+    #[token("+=")]
+    PlusEquals,
+    #[token("-=")]
+    MinusEquals,
+    #[token("*=")]
+    StarEquals,
+    #[token("/=")]
+    SlashEquals,
+    #[token("%=")]
+    PercentEquals,
+    #[token("&=")]
+    AmpersandEquals,
+    #[token("|=")]
+    PipeEquals,
+    #[token("^=")]
+    CaretEquals,
+    #[token("<<=")]
+    ShiftLeftEquals,
+    #[token(">>=")]
+    ShiftRightEquals,
 
     // comments
     #[regex(r"//.*")]
     LineComment,
-    #[token("/*")]
-    OpenComment,
-    #[token("*/")]
-    CloseComment,
+    /// A `/* */` comment, which may nest. It is `false` when the comment is
+    /// never closed, and runs to the end of the source.
+    #[token("/*", block_comment)]
+    BlockComment(bool),
+
+    // literals
+    #[regex("(0x[0-9a-fA-F_]+|0b[01_]+|[0-9][0-9_]*)(u32|i32|u|i)?")]
+    Integer,
+    #[regex(r"[0-9][0-9_]*\.[0-9][0-9_]*([eE][+-]?[0-9]+)?(f32|f64|lf|f)?")]
+    #[regex("[0-9][0-9_]*[eE][+-]?[0-9]+(f32|f64|lf|f)?")]
+    #[regex("[0-9][0-9_]*(f32|f64|lf|f)")]
+    Float,
+    #[token("true")]
+    True,
+    #[token("false")]
+    False,
+    #[regex(r#""([^"\\]|\\.)*""#)]
+    String,
 
     // misc
-    #[regex("[a-zA-Z][a-zA-Z0-9]*")]
+    #[regex(r"[_\p{XID_Start}]\p{XID_Continue}*")]
     Text,
+    /// Kept for the lossless syntax tree; `TokenStream::buffer` drops it.
+    #[regex(r"[ \t\r\n\f]+")]
+    Whitespace,
     #[error]
-    #[regex(r"[ \t\r\n\f]+", logos::skip)]
     Error,
 }
 
+/// Lex the rest of a block comment after its `/*`, up to the matching `*/`.
+/// Nothing inside it is lexed, so `//` does not hide the closing `*/`.
+fn block_comment(lex: &mut Lexer) -> Option<bool> {
+    let rest = lex.remainder();
+    let mut depth = 1;
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some((_, '*'))) => {
+                chars.next();
+                depth += 1;
+            },
+            ('*', Some((_, '/'))) => {
+                chars.next();
+                depth -= 1;
+                if depth == 0 {
+                    lex.bump(i + 2);
+                    return Some(true);
+                }
+            },
+            _ => {},
+        }
+    }
+    lex.bump(rest.len());
+    Some(false)
+}
+
+/// Words set aside for future language features. They lex as `Token::Text`,
+/// but are never valid identifiers.
+pub const RESERVED: &[&str] = &[
+    "enum", "impl", "loop", "match", "self", "static", "trait", "where",
+];
+
+pub fn is_reserved(word: &str) -> bool {
+    RESERVED.contains(&word)
+}
+
 #[derive(Debug, Clone)]
 pub struct TokenStream<'a> {
     rem: &'a [(Token, Span)],
     source: &'a str,
     prev: Option<&'a str>,
+    prev_span: Option<Span>,
 }
 
 impl<'a> TokenStream<'a> {
     pub fn buffer(source: &'a str) -> Vec<(Token, Span)> {
+        TokenStream::lossless_buffer(source)
+            .into_iter()
+            .filter(|(token, _)| *token != Token::Whitespace)
+            .collect()
+    }
+
+    /// Every token in the source, including whitespace.
+    pub fn lossless_buffer(source: &'a str) -> Vec<(Token, Span)> {
         let lexer = Token::lexer(source);
         lexer.spanned().collect()
     }
@@ -96,9 +248,15 @@ impl<'a> TokenStream<'a> {
             rem: buffer,
             source,
             prev: None,
+            prev_span: None,
         }
     }
 
+    /// The whole source being tokenized.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
     pub fn eof(&self) -> bool {
         self.peek().is_none()
     }
@@ -108,70 +266,85 @@ impl<'a> TokenStream<'a> {
         self.rem.get(skipped).map(|(token, _)| token)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&Token> {
         self.rem = &self.rem[self.skip_comments()..];
-        let (next, span) = self.rem.get(0)?;
+        let (next, span) = self.rem.first()?;
         self.prev = self.source.get(span.clone());
+        self.prev_span = Some(span.clone());
         self.rem = &self.rem[1..];
         Some(next)
     }
 
-    pub fn slice_prev(&self) -> Option<&str> {
+    pub fn slice_prev(&self) -> Option<&'a str> {
         self.prev
     }
 
-    pub fn slice(&self) -> Option<&str> {
-        let (_, span) = self.rem.get(0)?;
+    /// The byte range of the token most recently returned by `next`.
+    pub fn span_prev(&self) -> Option<Span> {
+        self.prev_span.clone()
+    }
+
+    /// The text of the token `next` would return.
+    pub fn slice(&self) -> Option<&'a str> {
+        let (_, span) = self.rem.get(self.skip_comments())?;
         self.source.get(span.clone())
     }
 
+    /// The byte range of the token `next` would return.
+    pub fn span(&self) -> Option<Span> {
+        let (_, span) = self.rem.get(self.skip_comments())?;
+        Some(span.clone())
+    }
+
+    /// Byte offset of the token `next` would return, or the end of the
+    /// source when there are no tokens left.
+    pub fn position(&self) -> usize {
+        self.span().map(|span| span.start).unwrap_or_else(|| self.source.len())
+    }
+
+    /// Byte offset of the end of the token most recently returned by
+    /// `next`, or zero if none has been.
+    pub fn prev_end(&self) -> usize {
+        self.prev_span.as_ref().map(|span| span.end).unwrap_or(0)
+    }
+
     pub fn skip_line_comments(&self) -> usize {
-        TokenStream::skip_line_comments_impl(&self.rem)
+        TokenStream::skip_line_comments_impl(self.rem)
     }
 
     fn skip_line_comments_impl(mut rem: &[(Token, Span)]) -> usize {
         let mut skipped = 0;
-        while let Some((Token::LineComment, _)) = rem.get(0) {
+        while let Some((Token::LineComment, _)) = rem.first() {
             skipped += 1;
             rem = &rem[1..];
         }
         skipped
     }
 
-    /// Determine how many tokens to skip due to comments
+    /// Determine how many tokens to skip due to comments.
     pub fn skip_comments(&self) -> usize {
-        let mut rem = self.rem;
-        rem = &rem[TokenStream::skip_line_comments_impl(rem)..];
-        let skipped = if let Some((Token::OpenComment, _)) = rem.get(0) {
-            rem = &rem[1..];
-            let mut depth = 0;
-            let mut exited = false;
-            while !exited {
-                match rem.get(0) {
-                    Some((Token::OpenComment, _)) => {
-                        depth += 1;
-                    },
-                    Some((Token::CloseComment, _)) => {
-                        if depth == 0 {
-                            exited = true;
-                        } else {
-                            depth -= 1;
-                        }
-                    },
-                    Some(_) => {},
-                    None => {
-                        panic!("encountered EOF inside comment");
-                    },
-                }
-                rem = &rem[1..];
-            }
-            // TODO: need to recurse/loop, as this won't handle all sequential comments
-            rem = &rem[TokenStream::skip_line_comments_impl(rem)..];
-            rem
-        } else {
-            rem
-        };
-        self.rem.len() - skipped.len()
+        self.rem.iter()
+            .take_while(|(token, _)| matches!(token, Token::LineComment | Token::BlockComment(_) | Token::Whitespace))
+            .count()
+    }
+
+    /// The span of each comment ahead of the next token.
+    pub fn comments(&self) -> Vec<Span> {
+        self.rem[..self.skip_comments()]
+            .iter()
+            .filter(|(token, _)| *token != Token::Whitespace)
+            .map(|(_, span)| span.clone())
+            .collect()
+    }
+
+    /// The span of a block comment ahead of the next token which is still
+    /// open at the end of the source.
+    pub fn unterminated_comment(&self) -> Option<Span> {
+        self.rem[..self.skip_comments()]
+            .iter()
+            .find(|(token, _)| *token == Token::BlockComment(false))
+            .map(|(_, span)| span.clone())
     }
 }
 
@@ -194,6 +367,72 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn parses_operators() {
+        let buffer: Vec<Token> = TokenStream::buffer("
+            a<<=b>>c>=d/e // f
+        ").iter().map(|(token, _span)| token.clone()).collect();
+
+        assert_eq!(buffer, vec![
+            Token::Text,
+            Token::ShiftLeftEquals,
+            Token::Text,
+            Token::ShiftRight,
+            Token::Text,
+            Token::GreaterThanEquals,
+            Token::Text,
+            Token::Slash,
+            Token::Text,
+            Token::LineComment,
+        ]);
+    }
+
+    #[test]
+    fn parses_literals() {
+        const SOURCE: &str = r#"
+            0 42u 0xFF_FFi32 0b1010 1.5 2.0e-3f32 1e10 3f 0..n true false "a \"b\""
+        "#;
+        let buffer = TokenStream::buffer(SOURCE);
+        let tokens: Vec<_> = buffer.iter()
+            .map(|(token, span)| (token.clone(), &SOURCE[span.clone()]))
+            .collect();
+
+        assert_eq!(tokens, vec![
+            (Token::Integer, "0"),
+            (Token::Integer, "42u"),
+            (Token::Integer, "0xFF_FFi32"),
+            (Token::Integer, "0b1010"),
+            (Token::Float, "1.5"),
+            (Token::Float, "2.0e-3f32"),
+            (Token::Float, "1e10"),
+            (Token::Float, "3f"),
+            (Token::Integer, "0"),
+            (Token::Range, ".."),
+            (Token::Text, "n"),
+            (Token::True, "true"),
+            (Token::False, "false"),
+            (Token::String, r#""a \"b\"""#),
+        ]);
+    }
+
+    #[test]
+    fn parses_identifiers() {
+        const SOURCE: &str = "base_color _unused uv0_scale größe _ x1";
+        let buffer = TokenStream::buffer(SOURCE);
+        let tokens: Vec<_> = buffer.iter()
+            .map(|(token, span)| (token.clone(), &SOURCE[span.clone()]))
+            .collect();
+
+        assert_eq!(tokens, vec![
+            (Token::Text, "base_color"),
+            (Token::Text, "_unused"),
+            (Token::Text, "uv0_scale"),
+            (Token::Text, "größe"),
+            (Token::Text, "_"),
+            (Token::Text, "x1"),
+        ]);
+    }
+
     #[test]
     fn skips_line_comments() {
         const SOURCE: &str = "
@@ -230,16 +469,12 @@ mod test {
 
         assert!(Iterator::eq(buffer.iter().map(|(t, _)| t), [
             Token::Uniform,
-            Token::OpenComment,
-            Token::Uniform,      // 1 space ahead
-            Token::Text,         // 2 spaces ahead
-            Token::CloseComment, // 3 spaces ahead
-            Token::Let,          // 4 spaces ahead
+            Token::BlockComment(true),
+            Token::Let,
         ].iter()));
         assert_eq!(stream.next().unwrap(), &Token::Uniform);
         assert_eq!(stream.skip_line_comments(), 0);
-        // when at "open comment", skip ahead 4 spaces
-        assert_eq!(stream.skip_comments(), 4);
+        assert_eq!(stream.skip_comments(), 1);
         assert_eq!(stream.next().unwrap(), &Token::Let);
     }
 
@@ -260,22 +495,16 @@ mod test {
 
         assert!(Iterator::eq(buffer.iter().map(|(t, _)| t), [
             Token::Uniform,
-            Token::OpenComment,
-            Token::OpenComment,
-            Token::CloseComment,
-            Token::Uniform,
-            Token::Text,
-            Token::CloseComment,
+            Token::BlockComment(true),
             Token::Let,
         ].iter()));
         assert_eq!(stream.next().unwrap(), &Token::Uniform);
         assert_eq!(stream.skip_line_comments(), 0);
-        assert_eq!(stream.skip_comments(), 6);
+        assert_eq!(stream.skip_comments(), 1);
         assert_eq!(stream.next().unwrap(), &Token::Let);
     }
 
     #[test]
-    #[ignore = "sequential comments not supported yet"]
     fn skips_sequential_block_comments() {
         const SOURCE: &str = "
             uniform // billy bob
@@ -291,12 +520,54 @@ mod test {
         let mut stream = TokenStream::new(&buffer, SOURCE);
 
         assert_eq!(stream.next().unwrap(), &Token::Uniform);
-        assert_eq!(stream.skip_comments(), 4, "buffer: {:?}", &buffer);
+        assert_eq!(stream.skip_comments(), 3, "buffer: {:?}", &buffer);
         assert_eq!(stream.next().unwrap(), &Token::Function);
-        assert_eq!(stream.skip_comments(), 5, "buffer: {:?}", &buffer);
+        assert_eq!(stream.skip_comments(), 2, "buffer: {:?}", &buffer);
         assert_eq!(stream.next().unwrap(), &Token::Let);
     }
 
+    #[test]
+    fn ignores_line_comments_in_block_comments() {
+        let tokens = |source: &str| -> Vec<Token> {
+            TokenStream::buffer(source).into_iter().map(|(token, _)| token).collect()
+        };
+        assert_eq!(tokens("/* // */"), [Token::BlockComment(true)]);
+        assert_eq!(tokens("/* a // b */ x"), [Token::BlockComment(true), Token::Text]);
+        assert_eq!(tokens("/* /* // */ */ x // /*"), [Token::BlockComment(true), Token::Text, Token::LineComment]);
+    }
+
+    #[test]
+    fn skips_unterminated_block_comments() {
+        const SOURCE: &str = "
+            uniform /* a */ /* b /* c */
+            let
+        ";
+        let buffer = TokenStream::buffer(SOURCE);
+        let mut stream = TokenStream::new(&buffer, SOURCE);
+
+        assert_eq!(stream.next().unwrap(), &Token::Uniform);
+        assert!(stream.eof());
+        let span = stream.unterminated_comment().unwrap();
+        assert_eq!(&SOURCE[span.start..], "/* b /* c */\n            let\n        ");
+        assert_eq!(stream.next(), None);
+
+        let buffer = TokenStream::buffer("let /* a */");
+        let stream = TokenStream::new(&buffer, "let /* a */");
+        assert_eq!(stream.unterminated_comment(), None);
+    }
+
+    #[test]
+    fn finds_comments_ahead() {
+        const SOURCE: &str = "uniform // a\n/* b /* c */ */ /* d";
+        let buffer = TokenStream::buffer(SOURCE);
+        let mut stream = TokenStream::new(&buffer, SOURCE);
+
+        assert_eq!(stream.comments(), []);
+        stream.next();
+        let comments: Vec<_> = stream.comments().into_iter().map(|span| &SOURCE[span]).collect();
+        assert_eq!(comments, ["// a", "/* b /* c */ */", "/* d"]);
+    }
+
     #[test]
     fn peek_skips_comments() {
         const SOURCE: &str = "
@@ -0,0 +1,78 @@
+//! Folds the syntax tree from outside the crate, which can only take nodes
+//! apart and build them through their public API.
+
+use xenovisor_shaderc::syntax::{
+    parse_module,
+    visit::{fold_expr, fold_statement, Fold},
+    Assignment,
+    Expr,
+    ExprKind,
+    ItemKind,
+    Statement,
+    StatementKind,
+};
+
+/// Removes parentheses, and expands compound assignments such as `x += y`
+/// into `x = x + y`.
+struct Desugar;
+
+impl Fold for Desugar {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        let expr = fold_expr(self, expr);
+        let span = expr.span();
+        match expr.into_kind() {
+            ExprKind::Group(inner) => *inner,
+            kind => Expr::new(kind, span),
+        }
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        let statement = fold_statement(self, statement);
+        let span = statement.span();
+        match statement.into_kind() {
+            StatementKind::Assignment(assignment) => {
+                let value = match assignment.operator() {
+                    Some(operator) => {
+                        let left = Box::new(assignment.target().clone());
+                        let right = Box::new(assignment.expression().clone());
+                        Expr::new(ExprKind::Binary(operator, left, right), assignment.expression().span())
+                    },
+                    None => assignment.expression().clone(),
+                };
+                let assignment = Assignment::new(assignment.target().clone(), None, value, assignment.span());
+                Statement::new(StatementKind::Assignment(assignment), span)
+            },
+            kind => Statement::new(kind, span),
+        }
+    }
+}
+
+#[test]
+fn folds_outside_the_crate() {
+    let module = parse_module("fn main() { let mut x = (1.0); x *= (x + 2.0); }").unwrap();
+    let module = Desugar.fold_module(module);
+    let function = match module.items()[0].kind() {
+        ItemKind::Function(function) => function,
+        kind => panic!("expected function, found {:?}", kind),
+    };
+    let statements = function.body().statements();
+    match statements[0].kind() {
+        StatementKind::Let(binding) => {
+            assert!(matches!(binding.expression().map(Expr::kind), Some(ExprKind::Literal(_))));
+        },
+        kind => panic!("expected let, found {:?}", kind),
+    }
+    match statements[1].kind() {
+        StatementKind::Assignment(assignment) => {
+            assert_eq!(assignment.operator(), None);
+            match assignment.expression().kind() {
+                ExprKind::Binary(_, left, right) => {
+                    assert!(matches!(left.kind(), ExprKind::Identifier(name) if name.str() == "x"));
+                    assert!(matches!(right.kind(), ExprKind::Binary(_, _, _)));
+                },
+                kind => panic!("expected binary expression, found {:?}", kind),
+            }
+        },
+        kind => panic!("expected assignment, found {:?}", kind),
+    }
+}